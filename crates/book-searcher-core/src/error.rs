@@ -0,0 +1,27 @@
+use tantivy::{query::QueryParserError, TantivyError};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("index error: {0}")]
+    Index(#[from] TantivyError),
+    #[error("raw data error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("raw data error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("raw data error: {0}")]
+    InvalidData(String),
+    #[error("mapping error: {0}")]
+    Mapping(String),
+    #[error("query error: {0}")]
+    Query(#[from] QueryParserError),
+    #[error("compressor not valid: {0}, should be one of none, lz4, zstd")]
+    InvalidCompressor(String),
+    #[error("index {0} was built with an older schema, rebuild it")]
+    OutdatedIndex(String),
+    #[error("no index found in {0}")]
+    IndexNotFound(String),
+}
@@ -0,0 +1,79 @@
+use crate::{Result, Searcher};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tantivy::{directory::WatchCallback, Directory, Index};
+
+/// Callbacks to observe a `Searcher`, e.g. to export metrics.
+pub trait SearcherHook: Send + Sync {
+    /// Called after each search with the number of matched books.
+    fn searched(&self, _total: usize) {}
+
+    /// Called once for each new commit picked up by the reader,
+    /// whether it is reloaded manually or because the commit was detected.
+    fn reloaded(&self) {}
+}
+
+impl Searcher {
+    pub fn set_hook(&mut self, hook: Arc<dyn SearcherHook>) -> Result<()> {
+        // The watcher also fires when it starts, only report new commits.
+        self.hook_opstamp = Arc::new(AtomicU64::new(self.index.load_metas()?.opstamp));
+        let index = self.index.clone();
+        let opstamp = self.hook_opstamp.clone();
+        let watched = hook.clone();
+        let handle = self.index.directory().watch(WatchCallback::new(move || {
+            report_reload(&index, &opstamp, watched.as_ref());
+        }))?;
+        self.hook = Some(hook);
+        self.watch_handle = Some(handle);
+        Ok(())
+    }
+
+    pub(crate) fn report_reload(&self) {
+        if let Some(hook) = &self.hook {
+            report_reload(&self.index, &self.hook_opstamp, hook.as_ref());
+        }
+    }
+}
+
+/// Report the last commit to the hook, unless the watcher or `reload` already did.
+fn report_reload(index: &Index, opstamp: &AtomicU64, hook: &dyn SearcherHook) {
+    if let Ok(metas) = index.load_metas() {
+        if opstamp.swap(metas.opstamp, Ordering::Relaxed) != metas.opstamp {
+            hook.reloaded();
+        }
+    }
+}
+
+#[test]
+fn test_reloaded_once_per_commit() {
+    use crate::test_book;
+
+    #[derive(Default)]
+    struct Reloads(AtomicU64);
+
+    impl SearcherHook for Reloads {
+        fn reloaded(&self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let reloads = Arc::new(Reloads::default());
+    let mut searcher = Searcher::new_in_ram().unwrap();
+    searcher.set_hook(reloads.clone()).unwrap();
+    let count = || reloads.0.load(Ordering::Relaxed);
+    assert_eq!(count(), 0);
+
+    // `index_books` reloads, whichever of it and the watcher comes first reports the commit
+    crate::index::index_books(&searcher, vec![test_book(1, "Dune")]);
+    assert_eq!(count(), 1);
+    // the watcher or another reload seeing the same commit again
+    searcher.report_reload();
+    searcher.reload().unwrap();
+    assert_eq!(count(), 1);
+
+    crate::index::index_books(&searcher, vec![test_book(2, "Emma")]);
+    searcher.report_reload();
+    assert_eq!(count(), 2);
+}
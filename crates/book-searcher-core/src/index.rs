@@ -1,13 +1,44 @@
-use crate::{always_merge_policy::AlwaysMergePolicy, Book, Searcher};
-use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
-use log::info;
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-    path::Path,
+use crate::{
+    always_merge_policy::AlwaysMergePolicy, lookup::normalize_isbns, Book, InputSource, Result,
+    Searcher, SCHEMA_VERSION,
 };
+use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
+use log::{error, info};
+use std::str::FromStr;
 use sysinfo::{System, SystemExt};
-use tantivy::doc;
+use tantivy::{
+    doc,
+    tokenizer::{PreTokenizedString, TextAnalyzer},
+    Index, IndexWriter, Term,
+};
+
+/// How documents are written into an existing index.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndexMode {
+    /// Always add a new document, even if the book is already indexed.
+    #[default]
+    Append,
+    /// Replace the existing documents that have the same `id`.
+    UpsertById,
+    /// Replace the existing documents that have the same `md5`.
+    UpsertByMd5,
+}
+
+impl FromStr for IndexMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "append" => Ok(Self::Append),
+            "upsert" | "upsert-id" => Ok(Self::UpsertById),
+            "upsert-md5" => Ok(Self::UpsertByMd5),
+            _ => Err(format!(
+                "index mode not valid: {:?}",
+                ["append", "upsert", "upsert-md5"]
+            )),
+        }
+    }
+}
 
 fn get_memory_arena_num_bytes() -> usize {
     let sys = System::new_all();
@@ -34,52 +65,21 @@ fn get_memory_arena_num_bytes() -> usize {
 }
 
 impl Searcher {
-    pub fn index(&mut self, csv_file: impl AsRef<Path>) {
-        let mut writer = self.index.writer(get_memory_arena_num_bytes()).unwrap();
+    pub fn index(&mut self, source: &InputSource) -> Result<()> {
+        let mut writer = self.index.writer(get_memory_arena_num_bytes())?;
         writer.set_merge_policy(Box::new(AlwaysMergePolicy));
 
-        let file = File::open(&csv_file).unwrap();
-        let reader = BufReader::new(file);
-
-        let mut rdr = csv::ReaderBuilder::new()
-            .has_headers(false)
-            .from_reader(reader);
-
-        let line_count = BufReader::new(File::open(&csv_file).unwrap())
-            .lines()
-            .count();
+        let books = source.books()?;
+        let bar = progress_bar(source)?;
 
-        let style = ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
-            .unwrap();
-        let bar = ProgressBar::new(line_count as u64)
-            .with_message(format!("Indexing {}", csv_file.as_ref().to_str().unwrap()))
-            .with_style(style);
-
-        for result in rdr.deserialize::<Book>().progress_with(bar) {
+        for result in books.progress_with(bar) {
             match result {
                 Ok(item) => {
                     if skip_this_book(&item) {
                         continue;
                     }
 
-                    let score_boost = get_book_score_boost(&item);
-                    if let Err(err) = writer.add_document(doc!(
-                        self.id => item.id,
-                        self.title => item.title,
-                        self.author => item.author,
-                        self.publisher => item.publisher,
-                        self.extension => item.extension,
-                        self.filesize => item.filesize,
-                        self.language => item.language,
-                        self.year => item.year,
-                        self.pages => item.pages,
-                        self.isbn => item.isbn,
-                        self.md5 => item.md5,
-                        self.ipfs_cid => item.ipfs_cid,
-                        self.cover_url => item.cover_url,
-                        self.score_boost => score_boost,
-                    )) {
+                    if let Err(err) = self.write_book(&writer, item) {
                         println!("{err}");
                     }
                 }
@@ -89,59 +89,29 @@ impl Searcher {
             }
         }
 
-        writer.commit().unwrap();
-        writer.wait_merging_threads().expect("merge complete");
+        commit(&mut writer)?;
+        writer.wait_merging_threads()?;
+        self.reload()
     }
 
-    pub fn index_background(&mut self, csv_file: impl AsRef<Path>) -> ProgressBar {
+    pub fn index_background(&mut self, source: &InputSource) -> Result<ProgressBar> {
         let searcher = self.to_owned();
 
-        let mut writer = self.index.writer(get_memory_arena_num_bytes()).unwrap();
-
-        let file = File::open(&csv_file).unwrap();
-        let reader = BufReader::new(file);
-
-        let mut rdr = csv::ReaderBuilder::new()
-            .has_headers(false)
-            .from_reader(reader);
+        let mut writer = self.index.writer(get_memory_arena_num_bytes())?;
 
-        let line_count = BufReader::new(File::open(&csv_file).unwrap())
-            .lines()
-            .count();
-
-        let style = ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
-            .unwrap();
-        let bar = ProgressBar::new(line_count as u64)
-            .with_message(format!("Indexing {}", csv_file.as_ref().to_str().unwrap()))
-            .with_style(style);
+        let books = source.books()?;
+        let bar = progress_bar(source)?;
 
         let bar_background = bar.clone();
         std::thread::spawn(move || {
-            for result in rdr.deserialize::<Book>().progress_with(bar_background) {
+            for result in books.progress_with(bar_background) {
                 match result {
                     Ok(item) => {
                         if skip_this_book(&item) {
                             continue;
                         }
 
-                        let score_boost = get_book_score_boost(&item);
-                        if let Err(err) = writer.add_document(doc!(
-                            searcher.id => item.id,
-                            searcher.title => item.title,
-                            searcher.author => item.author,
-                            searcher.publisher => item.publisher,
-                            searcher.extension => item.extension,
-                            searcher.filesize => item.filesize,
-                            searcher.language => item.language,
-                            searcher.year => item.year,
-                            searcher.pages => item.pages,
-                            searcher.isbn => item.isbn,
-                            searcher.md5 => item.md5,
-                            searcher.ipfs_cid => item.ipfs_cid,
-                            searcher.cover_url => item.cover_url,
-                            searcher.score_boost => score_boost,
-                        )) {
+                        if let Err(err) = searcher.write_book(&writer, item) {
                             println!("{err}");
                         }
                     }
@@ -151,12 +121,115 @@ impl Searcher {
                 }
             }
 
-            writer.commit().unwrap();
-            writer.wait_merging_threads().expect("merge complete");
+            if let Err(err) = commit(&mut writer) {
+                error!("Failed to commit index: {err}");
+                return;
+            }
+            if let Err(err) = writer.wait_merging_threads() {
+                error!("Failed to merge index: {err}");
+            }
+            if let Err(err) = searcher.reload() {
+                error!("Failed to reload index: {err}");
+            }
         });
 
-        bar
+        Ok(bar)
+    }
+
+    /// Delete all documents of the books with the given ids.
+    pub fn delete(&self, ids: &[u64]) -> Result<()> {
+        let mut writer: IndexWriter = self.index.writer(50_000_000)?;
+        for &id in ids {
+            writer.delete_term(Term::from_field_u64(self.id, id));
+        }
+        commit(&mut writer)?;
+        self.reload()
     }
+
+    fn write_book(&self, writer: &IndexWriter, mut item: Book) -> tantivy::Result<()> {
+        // md5 is matched as lowercase hex, whatever the case in the dump
+        item.md5 = item.md5.trim().to_ascii_lowercase();
+        match self.index_mode {
+            IndexMode::Append => {}
+            IndexMode::UpsertById => {
+                writer.delete_term(Term::from_field_u64(self.id, item.id));
+            }
+            IndexMode::UpsertByMd5 => {
+                // books without md5 can not be matched, never delete them all
+                if !item.md5.is_empty() {
+                    writer.delete_term(Term::from_field_text(self.md5, &item.md5));
+                }
+            }
+        }
+
+        let score_boost = get_book_score_boost(&item);
+        // stemmed in the language of the book, not known to the field analyzer
+        let mut latin = self.latin_tokenizer(Some(&item.language));
+        let title_stem = pre_tokenize(&mut latin, &item.title);
+        let author_stem = pre_tokenize(&mut latin, &item.author);
+        let publisher_stem = pre_tokenize(&mut latin, &item.publisher);
+        let isbns = normalize_isbns(&item.isbn);
+        let mut doc = doc!(
+            self.id => item.id,
+            self.title => item.title,
+            self.author => item.author,
+            self.publisher => item.publisher,
+            self.extension => item.extension,
+            self.filesize => item.filesize,
+            self.language => item.language,
+            self.year => item.year,
+            self.pages => item.pages,
+            self.isbn => item.isbn,
+            self.md5 => item.md5,
+            self.ipfs_cid => item.ipfs_cid,
+            self.cover_url => item.cover_url,
+            self.score_boost => score_boost,
+            self.title_stem => title_stem,
+            self.author_stem => author_stem,
+            self.publisher_stem => publisher_stem,
+        );
+        for isbn in isbns {
+            doc.add_text(self.isbn_code, isbn);
+        }
+        writer.add_document(doc)?;
+        Ok(())
+    }
+}
+
+/// Commit with the schema version as payload, checked when the index is opened.
+pub(crate) fn commit(writer: &mut IndexWriter) -> tantivy::Result<u64> {
+    let mut prepared = writer.prepare_commit()?;
+    prepared.set_payload(&SCHEMA_VERSION.to_string());
+    prepared.commit()
+}
+
+/// Schema version of the last commit, `None` before the first one.
+pub(crate) fn schema_version(index: &Index) -> tantivy::Result<Option<u32>> {
+    let payload = index.load_metas()?.payload;
+    Ok(payload.and_then(|payload| payload.parse().ok()))
+}
+
+fn pre_tokenize(analyzer: &mut TextAnalyzer, text: &str) -> PreTokenizedString {
+    let mut tokens = Vec::new();
+    analyzer
+        .token_stream(text)
+        .process(&mut |token| tokens.push(token.clone()));
+    PreTokenizedString {
+        text: text.to_owned(),
+        tokens,
+    }
+}
+
+fn progress_bar(source: &InputSource) -> Result<ProgressBar> {
+    let count = source.count()?;
+
+    let style = ProgressStyle::default_bar()
+        .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
+        .unwrap();
+    let bar = ProgressBar::new(count as u64)
+        .with_message(format!("Indexing {}", source.path().display()))
+        .with_style(style);
+    Ok(bar)
 }
 
 // score = origin_score * (10 + score_boost).log10()
@@ -212,6 +285,8 @@ fn skip_this_book(book: &Book) -> bool {
 
 #[test]
 fn test_csv_der() {
+    use std::{fs::File, io::BufReader};
+
     let file = File::open("books.csv").unwrap();
     let reader = BufReader::new(file);
 
@@ -226,3 +301,70 @@ fn test_csv_der() {
     }
     println!("{:?}", rdr.position());
 }
+
+/// Write the books into the index of `searcher` and make them searchable.
+#[cfg(test)]
+pub(crate) fn index_books(searcher: &Searcher, books: Vec<Book>) {
+    let mut writer = searcher.index.writer(15_000_000).unwrap();
+    for book in books {
+        searcher.write_book(&writer, book).unwrap();
+    }
+    commit(&mut writer).unwrap();
+    searcher.reload().unwrap();
+}
+
+#[test]
+fn test_upsert_and_delete() {
+    let book = |id, title, md5: &str| Book {
+        md5: md5.to_owned(),
+        ..crate::test_book(id, title)
+    };
+
+    let mut searcher = Searcher::new_in_ram().unwrap();
+    index_books(
+        &searcher,
+        vec![book(1, "first", "aaa"), book(2, "second", "bbb")],
+    );
+    assert_eq!(searcher.num_docs(), 2);
+
+    searcher.index_mode = IndexMode::UpsertById;
+    index_books(&searcher, vec![book(1, "first edition", "ccc")]);
+    assert_eq!(searcher.num_docs(), 2);
+    assert_eq!(
+        searcher.get_by_id(1).unwrap().unwrap().title,
+        "first edition"
+    );
+
+    searcher.index_mode = IndexMode::UpsertByMd5;
+    index_books(&searcher, vec![book(3, "second edition", "bbb")]);
+    assert_eq!(searcher.num_docs(), 2);
+    assert!(searcher.get_by_id(2).unwrap().is_none());
+    assert_eq!(searcher.get_by_md5("BBB").unwrap().unwrap().id, 3);
+
+    // md5 in another case replaces the book too
+    index_books(&searcher, vec![book(6, "third edition", " BbB")]);
+    assert_eq!(searcher.num_docs(), 2);
+    let replaced = searcher.get_by_md5("bbb").unwrap().unwrap();
+    assert_eq!((replaced.id, replaced.md5.as_str()), (6, "bbb"));
+
+    // books without md5 never replace each other
+    index_books(
+        &searcher,
+        vec![book(4, "no md5", ""), book(5, "no md5", "")],
+    );
+    assert_eq!(searcher.num_docs(), 4);
+
+    searcher.delete(&[1, 4]).unwrap();
+    assert_eq!(searcher.num_docs(), 2);
+    assert!(searcher.get_by_id(1).unwrap().is_none());
+    assert!(searcher.get_by_id(6).unwrap().is_some());
+}
+
+#[test]
+fn test_index_mode_from_str() {
+    assert_eq!("append".parse(), Ok(IndexMode::Append));
+    assert_eq!("upsert".parse(), Ok(IndexMode::UpsertById));
+    assert_eq!("upsert-id".parse(), Ok(IndexMode::UpsertById));
+    assert_eq!("upsert-md5".parse(), Ok(IndexMode::UpsertByMd5));
+    assert!("replace".parse::<IndexMode>().is_err());
+}
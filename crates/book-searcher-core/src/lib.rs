@@ -1,19 +1,50 @@
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DefaultOnError, DefaultOnNull};
-use std::path::Path;
-pub use tantivy::store::Compressor;
+use std::{
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicU64, Arc},
+};
 use tantivy::{
-    query::QueryParser, schema::*, store::ZstdCompressor, tokenizer::TextAnalyzer, Index,
-    TantivyError,
+    directory::WatchHandle,
+    query::QueryParser,
+    schema::*,
+    store::ZstdCompressor,
+    tokenizer::{TextAnalyzer, TokenizerManager},
+    Index, IndexReader, TantivyError,
+};
+pub use tantivy::{store::Compressor, ReloadPolicy};
+use tantivy_meta_tokenizer::{
+    get_latin_tokenizer, get_tokenizer_with, TokenizerOptions, META_LATIN_TOKENIZER, META_TOKENIZER,
 };
-use tantivy_meta_tokenizer::{get_tokenizer, META_TOKENIZER};
+pub use tantivy_meta_tokenizer::{stemmer_language, Language, LatinOptions};
 
 mod always_merge_policy;
+mod error;
+mod hook;
 pub mod index;
+mod lookup;
+pub mod mapping;
 pub mod search;
+pub mod source;
+mod stats;
+
+pub use error::{Error, Result};
+pub use hook::SearcherHook;
+pub use index::IndexMode;
+pub use mapping::FieldMapping;
+pub use source::{InputFormat, InputSource};
+pub use stats::IndexStats;
+
+/// Boost of matches in title, author and publisher over matches in their stemmed subfields.
+pub(crate) const EXACT_BOOST: f32 = 2.0;
+
+/// Version of the index schema, bumped when it changes so that old indexes must be rebuilt.
+pub const SCHEMA_VERSION: u32 = 3;
 
 #[serde_as]
 #[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(default)]
 pub struct Book {
     pub id: u64,
 
@@ -81,14 +112,59 @@ impl From<(&Schema, Document)> for Book {
     }
 }
 
+fn build_schema() -> Schema {
+    let text_indexing = TextFieldIndexing::default()
+        .set_tokenizer(META_TOKENIZER)
+        .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    let text_options = TextOptions::default()
+        .set_indexing_options(text_indexing)
+        .set_stored();
+
+    let mut schema_builder = Schema::builder();
+    schema_builder.add_u64_field("id", INDEXED | STORED | FAST);
+    schema_builder.add_text_field("title", text_options.clone());
+    schema_builder.add_text_field("author", text_options.clone());
+    schema_builder.add_text_field("publisher", text_options);
+    schema_builder.add_text_field("extension", STRING | STORED | FAST);
+    schema_builder.add_u64_field("filesize", INDEXED | STORED | FAST);
+    schema_builder.add_text_field("language", TEXT | STORED | FAST);
+    schema_builder.add_u64_field("year", INDEXED | STORED | FAST);
+    schema_builder.add_u64_field("pages", INDEXED | STORED | FAST);
+    schema_builder.add_text_field("isbn", TEXT | STORED);
+    schema_builder.add_text_field("md5", STRING | STORED);
+    schema_builder.add_text_field("ipfs_cid", STRING | STORED);
+    schema_builder.add_text_field("cover_url", STORED);
+    schema_builder.add_u64_field("score_boost", FAST);
+
+    let stem_indexing = TextFieldIndexing::default()
+        .set_tokenizer(META_LATIN_TOKENIZER)
+        .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    let stem_options = TextOptions::default().set_indexing_options(stem_indexing);
+    schema_builder.add_text_field("title_stem", stem_options.clone());
+    schema_builder.add_text_field("author_stem", stem_options.clone());
+    schema_builder.add_text_field("publisher_stem", stem_options);
+    // normalized ISBNs, one term each
+    schema_builder.add_text_field("isbn_code", STRING);
+    schema_builder.build()
+}
+
 #[derive(Clone)]
 pub struct Searcher {
     pub compressor: Compressor,
+    pub index_mode: IndexMode,
 
     index: Index,
+    index_dir: PathBuf,
+    reader: IndexReader,
     schema: Schema,
     query_parser: QueryParser,
+    query_tokenizers: TokenizerManager,
     tokenizer: TextAnalyzer,
+    latin_options: LatinOptions,
+    hook: Option<Arc<dyn SearcherHook>>,
+    // opstamp of the last commit reported to the hook
+    hook_opstamp: Arc<AtomicU64>,
+    watch_handle: Option<WatchHandle>,
 
     // fields
     id: Field,
@@ -105,61 +181,136 @@ pub struct Searcher {
     ipfs_cid: Field,
     cover_url: Field,
     score_boost: Field,
+    // latin words folded and stemmed
+    title_stem: Field,
+    author_stem: Field,
+    publisher_stem: Field,
+    isbn_code: Field,
 }
 
 impl Searcher {
-    pub fn new(index_dir: impl AsRef<Path>) -> Self {
-        let text_indexing = TextFieldIndexing::default()
-            .set_tokenizer(META_TOKENIZER)
-            .set_index_option(IndexRecordOption::WithFreqsAndPositions);
-        let text_options = TextOptions::default()
-            .set_indexing_options(text_indexing)
-            .set_stored();
-
-        let mut schema_builder = Schema::builder();
-        let id = schema_builder.add_u64_field("id", INDEXED | STORED);
-        let title = schema_builder.add_text_field("title", text_options.clone());
-        let author = schema_builder.add_text_field("author", text_options.clone());
-        let publisher = schema_builder.add_text_field("publisher", text_options);
-        let extension = schema_builder.add_text_field("extension", STRING | STORED);
-        let filesize = schema_builder.add_u64_field("filesize", STORED);
-        let language = schema_builder.add_text_field("language", TEXT | STORED);
-        let year = schema_builder.add_u64_field("year", STORED);
-        let pages = schema_builder.add_u64_field("pages", STORED | FAST);
-        let isbn = schema_builder.add_text_field("isbn", TEXT | STORED);
-        let md5 = schema_builder.add_text_field("md5", STORED);
-        let ipfs_cid = schema_builder.add_text_field("ipfs_cid", STORED);
-        let cover_url = schema_builder.add_text_field("cover_url", STORED);
-        let score_boost = schema_builder.add_u64_field("score_boost", FAST);
-        let schema = schema_builder.build();
+    pub fn new(index_dir: impl AsRef<Path>) -> Result<Self> {
+        let schema = build_schema();
 
         // open or create index
         let index_dir = index_dir.as_ref();
-        let mut index = Index::open_in_dir(index_dir).unwrap_or_else(|err| {
-            if let TantivyError::OpenDirectoryError(_) | TantivyError::OpenReadError(_) = err {
-                std::fs::create_dir_all(index_dir).expect("create index directory");
-                Index::create_in_dir(index_dir, schema.clone()).unwrap()
-            } else {
-                panic!("Error opening index: {err:?}")
+        let index = match Index::open_in_dir(index_dir) {
+            Ok(index) => index,
+            Err(TantivyError::OpenDirectoryError(_) | TantivyError::OpenReadError(_)) => {
+                std::fs::create_dir_all(index_dir)?;
+                let index = Index::create_in_dir(index_dir, schema.clone())?;
+                // an empty commit to record the schema version
+                index::commit(&mut index.writer_with_num_threads(1, 15_000_000)?)?;
+                index
+            }
+            Err(err) => return Err(err.into()),
+        };
+        Self::with_checked_index(index, index_dir, schema)
+    }
+
+    /// Open the index in `index_dir` like `new`, but fail instead of creating a missing one.
+    pub fn open(index_dir: impl AsRef<Path>) -> Result<Self> {
+        let index_dir = index_dir.as_ref();
+        let index = match Index::open_in_dir(index_dir) {
+            Ok(index) => index,
+            Err(TantivyError::OpenDirectoryError(_) | TantivyError::OpenReadError(_)) => {
+                return Err(Error::IndexNotFound(index_dir.display().to_string()));
             }
-        });
+            Err(err) => return Err(err.into()),
+        };
+        Self::with_checked_index(index, index_dir, build_schema())
+    }
 
-        let tokenizer = get_tokenizer();
+    fn with_checked_index(index: Index, index_dir: &Path, schema: Schema) -> Result<Self> {
+        // fields are looked up by the ids of `schema`, older indexes may differ
+        if index::schema_version(&index)? != Some(SCHEMA_VERSION) {
+            return Err(Error::OutdatedIndex(index_dir.display().to_string()));
+        }
+        Self::with_index(index, index_dir, schema)
+    }
+
+    /// Searcher of an empty index kept in memory.
+    #[cfg(test)]
+    pub(crate) fn new_in_ram() -> Result<Self> {
+        let schema = build_schema();
+        let index = Index::create_in_ram(schema.clone());
+        Self::with_index(index, Path::new(""), schema)
+    }
+
+    /// Searcher of an in-memory index holding `books`.
+    #[cfg(test)]
+    pub(crate) fn with_books(books: Vec<Book>) -> Self {
+        let searcher = Self::new_in_ram().unwrap();
+        index::index_books(&searcher, books);
+        searcher
+    }
+
+    /// Ids of the first ten books found by `query`, best first.
+    #[cfg(test)]
+    pub(crate) fn search_ids(&self, query: &search::SearchQuery) -> Vec<u64> {
+        let result = self.search(query, 10, 0).unwrap();
+        result.books.into_iter().map(|hit| hit.book.id).collect()
+    }
+
+    fn with_index(mut index: Index, index_dir: &Path, schema: Schema) -> Result<Self> {
+        let field = |name| schema.get_field(name).unwrap();
+        let id = field("id");
+        let title = field("title");
+        let author = field("author");
+        let publisher = field("publisher");
+        let extension = field("extension");
+        let filesize = field("filesize");
+        let language = field("language");
+        let year = field("year");
+        let pages = field("pages");
+        let isbn = field("isbn");
+        let md5 = field("md5");
+        let ipfs_cid = field("ipfs_cid");
+        let cover_url = field("cover_url");
+        let score_boost = field("score_boost");
+        let title_stem = field("title_stem");
+        let author_stem = field("author_stem");
+        let publisher_stem = field("publisher_stem");
+        let isbn_code = field("isbn_code");
+
+        index.tokenizers().register(
+            META_TOKENIZER,
+            get_tokenizer_with(TokenizerOptions::default()),
+        );
+
+        let latin_options = LatinOptions::default();
         index
             .tokenizers()
-            .register(META_TOKENIZER, tokenizer.clone());
+            .register(META_LATIN_TOKENIZER, get_latin_tokenizer(latin_options));
+
+        let tokenizer = get_tokenizer_with(TokenizerOptions::for_query());
+        let query_tokenizers = TokenizerManager::default();
+        query_tokenizers.register(META_TOKENIZER, tokenizer.clone());
+        query_tokenizers.register(META_LATIN_TOKENIZER, get_latin_tokenizer(latin_options));
         _ = index.set_default_multithread_executor();
 
-        let mut query_parser = QueryParser::for_index(&index, vec![title, author, publisher, isbn]);
-        query_parser.set_conjunction_by_default();
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommit)
+            .try_into()?;
 
-        Self {
+        let query_parser = new_query_parser(&schema, query_tokenizers.clone());
+
+        Ok(Self {
             compressor: Compressor::None,
+            index_mode: IndexMode::Append,
 
             index,
+            index_dir: index_dir.to_owned(),
+            reader,
             schema,
             query_parser,
+            query_tokenizers,
             tokenizer,
+            latin_options,
+            hook: None,
+            hook_opstamp: Arc::default(),
+            watch_handle: None,
 
             id,
             title,
@@ -175,10 +326,14 @@ impl Searcher {
             ipfs_cid,
             cover_url,
             score_boost,
-        }
+            title_stem,
+            author_stem,
+            publisher_stem,
+            isbn_code,
+        })
     }
 
-    pub fn set_compressor(&mut self, compressor: &str) {
+    pub fn set_compressor(&mut self, compressor: &str) -> Result<()> {
         let compressor = match compressor {
             "none" => Compressor::None,
             "lz4" => Compressor::Lz4,
@@ -186,12 +341,155 @@ impl Searcher {
                 if compressor.starts_with("zstd") {
                     Compressor::Zstd(ZstdCompressor::default())
                 } else {
-                    println!("compressor not valid: {:#?}", ["none", "lz4", "zstd",]);
-                    std::process::exit(1);
+                    return Err(Error::InvalidCompressor(compressor.to_owned()));
                 }
             }
         };
 
         self.index.settings_mut().docstore_compression = compressor;
+        Ok(())
+    }
+
+    /// Whether to index the original traditional Chinese words next to the simplified ones,
+    /// enabled by default. Queries match both forms either way.
+    pub fn set_keep_traditional(&mut self, keep_traditional: bool) {
+        self.index.tokenizers().register(
+            META_TOKENIZER,
+            get_tokenizer_with(TokenizerOptions {
+                keep_traditional,
+                ..Default::default()
+            }),
+        );
+    }
+
+    /// Set ascii folding and the default stemmer language of the stemmed subfields.
+    ///
+    /// Books are stemmed in their own language when it has a stemmer, queries in the
+    /// language they filter on, both fall back to `options.stemmer`.
+    pub fn set_latin_options(&mut self, options: LatinOptions) {
+        self.latin_options = options;
+        self.index
+            .tokenizers()
+            .register(META_LATIN_TOKENIZER, get_latin_tokenizer(options));
+        self.query_tokenizers
+            .register(META_LATIN_TOKENIZER, get_latin_tokenizer(options));
+    }
+
+    /// Latin analyzer stemming in `language` if it has a stemmer and stemming is enabled.
+    pub(crate) fn latin_tokenizer(&self, language: Option<&str>) -> TextAnalyzer {
+        let mut options = self.latin_options;
+        if options.stemmer.is_some() {
+            if let Some(stemmer) = language.and_then(stemmer_language) {
+                options.stemmer = Some(stemmer);
+            }
+        }
+        get_latin_tokenizer(options)
     }
+
+    /// Query parser stemming latin words in `language`, see `latin_tokenizer`.
+    pub(crate) fn query_parser(&self, language: Option<&str>) -> QueryParser {
+        if language.is_none() {
+            return self.query_parser.clone();
+        }
+        let tokenizers = TokenizerManager::default();
+        tokenizers.register(META_TOKENIZER, self.tokenizer.clone());
+        tokenizers.register(META_LATIN_TOKENIZER, self.latin_tokenizer(language));
+        new_query_parser(&self.schema, tokenizers)
+    }
+
+    /// Rebuild the shared reader with the given reload policy.
+    ///
+    /// With `ReloadPolicy::Manual`, new commits only become visible after `reload`.
+    pub fn set_reload_policy(&mut self, policy: ReloadPolicy) -> Result<()> {
+        self.reader = self
+            .index
+            .reader_builder()
+            .reload_policy(policy)
+            .try_into()?;
+        Ok(())
+    }
+
+    /// Reload the shared reader to pick up the latest commit.
+    pub fn reload(&self) -> Result<()> {
+        self.reader.reload()?;
+        self.report_reload();
+        Ok(())
+    }
+}
+
+/// Book with only an id and a title, other fields are set with struct update syntax.
+#[cfg(test)]
+pub(crate) fn test_book(id: u64, title: &str) -> Book {
+    Book {
+        id,
+        title: title.to_owned(),
+        ..Default::default()
+    }
+}
+
+/// Parser of the query syntax over title, author, publisher and isbn.
+fn new_query_parser(schema: &Schema, tokenizers: TokenizerManager) -> QueryParser {
+    let field = |name| schema.get_field(name).unwrap();
+    let exact_fields = ["title", "author", "publisher", "isbn"].map(field);
+    let stem_fields = ["title_stem", "author_stem", "publisher_stem"].map(field);
+
+    // words match either as written or stemmed, exact matches rank higher
+    let mut query_parser = QueryParser::new(
+        schema.clone(),
+        exact_fields.into_iter().chain(stem_fields).collect(),
+        tokenizers,
+    );
+    for field in exact_fields {
+        query_parser.set_field_boost(field, EXACT_BOOST);
+    }
+    query_parser.set_conjunction_by_default();
+    query_parser
+}
+
+#[test]
+fn test_manual_reload() {
+    let mut searcher = Searcher::new_in_ram().unwrap();
+    searcher.set_reload_policy(ReloadPolicy::Manual).unwrap();
+
+    let mut writer: tantivy::IndexWriter = searcher.index.writer(15_000_000).unwrap();
+    writer
+        .add_document(tantivy::doc!(searcher.id => 1u64, searcher.title => "Dune"))
+        .unwrap();
+    writer.commit().unwrap();
+    // clones share the reader, the commit is not visible to any of them yet
+    let clone = searcher.clone();
+    assert_eq!(searcher.num_docs(), 0);
+
+    clone.reload().unwrap();
+    assert_eq!(searcher.num_docs(), 1);
+}
+
+#[test]
+fn test_schema_version() {
+    let dir = std::env::temp_dir().join(format!("book-searcher-test-{}", std::process::id()));
+    _ = std::fs::remove_dir_all(&dir);
+
+    let searcher = Searcher::new(&dir).unwrap();
+    assert_eq!(
+        searcher.stats().unwrap().schema_version,
+        Some(SCHEMA_VERSION)
+    );
+    searcher.delete(&[1]).unwrap();
+    drop(searcher);
+    assert!(Searcher::new(&dir).is_ok());
+    assert!(Searcher::open(&dir).is_ok());
+    assert!(matches!(
+        Searcher::open(dir.join("missing")),
+        Err(Error::IndexNotFound(_))
+    ));
+    assert!(!dir.join("missing").exists());
+
+    // an index committed without the version
+    let index = Index::open_in_dir(&dir).unwrap();
+    let mut writer: tantivy::IndexWriter = index.writer(15_000_000).unwrap();
+    writer.commit().unwrap();
+    drop(writer);
+    assert!(matches!(Searcher::new(&dir), Err(Error::OutdatedIndex(_))));
+
+    std::fs::remove_dir_all(&dir).unwrap();
 }
@@ -0,0 +1,127 @@
+use crate::{Book, Result, Searcher};
+use tantivy::{
+    collector::TopDocs,
+    query::{BooleanQuery, Query, TermQuery},
+    schema::IndexRecordOption,
+    Term,
+};
+
+/// Most books returned by a lookup that may match several books, like ISBN.
+const MAX_LOOKUP_BOOKS: usize = 100;
+
+impl Searcher {
+    pub fn get_by_id(&self, id: u64) -> Result<Option<Book>> {
+        let term = Term::from_field_u64(self.id, id);
+        self.get_one(&TermQuery::new(term, IndexRecordOption::Basic))
+    }
+
+    /// Look up a book by md5, ignoring the case of the hex digits.
+    pub fn get_by_md5(&self, md5: &str) -> Result<Option<Book>> {
+        // indexed as lowercase
+        let term = Term::from_field_text(self.md5, &md5.trim().to_ascii_lowercase());
+        self.get_one(&TermQuery::new(term, IndexRecordOption::Basic))
+    }
+
+    /// Look up all books with the ISBN, e.g. editions in different formats.
+    ///
+    /// Hyphens and spaces are ignored, books with any of several ISBNs separated
+    /// by `,` or `;` are returned.
+    pub fn get_by_isbn(&self, isbn: &str) -> Result<Vec<Book>> {
+        let Some(query) = self.isbn_query(isbn) else {
+            return Ok(vec![]);
+        };
+        self.get_many(&query, MAX_LOOKUP_BOOKS)
+    }
+
+    /// Query matching books with any of the ISBNs, `None` if there is none.
+    pub(crate) fn isbn_query(&self, isbn: &str) -> Option<BooleanQuery> {
+        let terms: Vec<Term> = normalize_isbns(isbn)
+            .iter()
+            .map(|isbn| Term::from_field_text(self.isbn_code, isbn))
+            .collect();
+        if terms.is_empty() {
+            return None;
+        }
+        Some(BooleanQuery::new_multiterms_query(terms))
+    }
+
+    pub fn get_by_cid(&self, cid: &str) -> Result<Option<Book>> {
+        let term = Term::from_field_text(self.ipfs_cid, cid.trim());
+        self.get_one(&TermQuery::new(term, IndexRecordOption::Basic))
+    }
+
+    fn get_one(&self, query: &dyn Query) -> Result<Option<Book>> {
+        Ok(self.get_many(query, 1)?.pop())
+    }
+
+    fn get_many(&self, query: &dyn Query, limit: usize) -> Result<Vec<Book>> {
+        let searcher = self.reader.searcher();
+        let top_docs = searcher.search(query, &TopDocs::with_limit(limit))?;
+        top_docs
+            .into_iter()
+            .map(|(_, doc_address)| {
+                let doc = searcher.doc(doc_address)?;
+                Ok((&self.schema, doc).into())
+            })
+            .collect()
+    }
+}
+
+/// Split a value like `978-7-5366-9293-0, 7536692935` into ISBNs without
+/// hyphens and spaces, with an uppercase `X` check digit.
+pub(crate) fn normalize_isbns(isbn: &str) -> Vec<String> {
+    let mut isbns: Vec<String> = isbn
+        .split([',', ';'])
+        .map(|isbn| {
+            isbn.chars()
+                .filter(|c| *c != '-' && !c.is_whitespace())
+                .map(|c| c.to_ascii_uppercase())
+                .collect()
+        })
+        .filter(|isbn: &String| !isbn.is_empty())
+        .collect();
+    isbns.sort();
+    isbns.dedup();
+    isbns
+}
+
+#[test]
+fn test_normalize_isbns() {
+    assert_eq!(normalize_isbns("978-7-5366-9293-0"), ["9787536692930"]);
+    assert_eq!(
+        normalize_isbns(" 7536692935,9787536692930; 0-8044-2957-x ,"),
+        ["080442957X", "7536692935", "9787536692930"]
+    );
+    assert!(normalize_isbns(" - ").is_empty());
+}
+
+#[test]
+fn test_get_by_isbn() {
+    let book = |id, isbn: &str| Book {
+        isbn: isbn.to_owned(),
+        ..crate::test_book(id, "三体")
+    };
+    let searcher = Searcher::with_books(vec![
+        book(1, "978-7-5366-9293-0"),
+        book(2, "7536692935,9787536692930"),
+        book(3, "9787229030933"),
+        book(4, ""),
+    ]);
+
+    let ids = |isbn| {
+        let mut ids: Vec<u64> = searcher
+            .get_by_isbn(isbn)
+            .unwrap()
+            .into_iter()
+            .map(|book| book.id)
+            .collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(ids("9787536692930"), [1, 2]);
+    assert_eq!(ids("978 7 5366 9293 0"), [1, 2]);
+    assert_eq!(ids("7-5366-9293-5"), [2]);
+    assert_eq!(ids("9787229030933;7536692935"), [2, 3]);
+    assert!(ids("").is_empty());
+    assert!(ids("978").is_empty());
+}
@@ -0,0 +1,220 @@
+use crate::{Book, Error, Result};
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path};
+
+/// [`Book`] fields in the column order of csv files without header.
+pub const BOOK_FIELDS: [&str; 13] = [
+    "id",
+    "title",
+    "author",
+    "publisher",
+    "extension",
+    "filesize",
+    "language",
+    "year",
+    "pages",
+    "isbn",
+    "ipfs_cid",
+    "cover_url",
+    "md5",
+];
+
+/// Maps the columns of raw data to [`Book`] fields.
+///
+/// ```toml
+/// [fields.title]
+/// column = "Title"
+/// transforms = ["trim"]
+///
+/// [fields.language]
+/// column = "lang"
+/// default = "english"
+/// transforms = ["trim", "lowercase"]
+/// ```
+///
+/// Fields not listed are read from the column with the same name, or from the
+/// same position for csv without header. Other columns are ignored.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct FieldMapping {
+    #[serde(default)]
+    pub fields: HashMap<String, FieldRule>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FieldRule {
+    /// Column name, or zero-based column index for csv without header.
+    pub column: Option<String>,
+    /// Value used when the column is missing or empty.
+    pub default: Option<String>,
+    pub transforms: Vec<Transform>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transform {
+    Trim,
+    Lowercase,
+    Uppercase,
+}
+
+impl Transform {
+    fn apply(&self, value: String) -> String {
+        match self {
+            Self::Trim => value.trim().to_owned(),
+            Self::Lowercase => value.to_lowercase(),
+            Self::Uppercase => value.to_uppercase(),
+        }
+    }
+}
+
+impl FieldMapping {
+    /// Load mapping from a `.toml` or `.json` file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let mapping: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&content)?,
+            _ => toml::from_str(&content).map_err(|err| Error::Mapping(err.to_string()))?,
+        };
+
+        if let Some(field) = mapping
+            .fields
+            .keys()
+            .find(|field| !BOOK_FIELDS.contains(&field.as_str()))
+        {
+            return Err(Error::Mapping(format!(
+                "unknown field: {field}, should be one of {BOOK_FIELDS:?}"
+            )));
+        }
+
+        Ok(mapping)
+    }
+
+    /// Build a [`Book`] from a row, `get` returns the value of a column.
+    ///
+    /// If `by_index` is true, columns are addressed by their index.
+    pub(crate) fn map<'a>(
+        &self,
+        by_index: bool,
+        get: impl Fn(&str) -> Option<&'a str>,
+    ) -> Result<Book> {
+        let mut book = Book::default();
+
+        for (idx, &field) in BOOK_FIELDS.iter().enumerate() {
+            let rule = self.fields.get(field);
+            let column = match rule.and_then(|rule| rule.column.as_deref()) {
+                Some(column) => column.to_owned(),
+                None if by_index => idx.to_string(),
+                None => field.to_owned(),
+            };
+
+            let mut value = get(&column).unwrap_or_default().to_owned();
+            if let Some(rule) = rule {
+                value = rule.transforms.iter().fold(value, |v, t| t.apply(v));
+                if value.is_empty() {
+                    if let Some(ref default) = rule.default {
+                        value = default.clone();
+                    }
+                }
+            }
+
+            set_book_field(&mut book, field, value)?;
+        }
+
+        Ok(book)
+    }
+}
+
+fn set_book_field(book: &mut Book, field: &str, value: String) -> Result<()> {
+    match field {
+        "id" => {
+            book.id = value
+                .trim()
+                .parse()
+                .map_err(|_| Error::InvalidData(format!("id not valid: {value:?}")))?
+        }
+        "title" => book.title = value,
+        "author" => book.author = value,
+        "publisher" => book.publisher = value,
+        "extension" => book.extension = value,
+        "filesize" => book.filesize = value.trim().parse().unwrap_or_default(),
+        "language" => book.language = value,
+        "year" => book.year = value.trim().parse().unwrap_or_default(),
+        "pages" => book.pages = value.trim().parse().unwrap_or_default(),
+        "isbn" => book.isbn = value,
+        "ipfs_cid" => book.ipfs_cid = value,
+        "cover_url" => book.cover_url = value,
+        "md5" => book.md5 = value,
+        _ => unreachable!("unknown book field: {field}"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_map_by_name() {
+    let mapping: FieldMapping = toml::from_str(
+        r#"
+        [fields.title]
+        column = "Title"
+        transforms = ["trim"]
+
+        [fields.language]
+        column = "lang"
+        default = "english"
+        transforms = ["trim", "lowercase"]
+        "#,
+    )
+    .unwrap();
+
+    let row = HashMap::from([
+        ("id", "7"),
+        ("Title", "  Dune "),
+        ("lang", " "),
+        ("year", "1965"),
+    ]);
+    let book = mapping
+        .map(false, |column| row.get(column).copied())
+        .unwrap();
+    assert_eq!(book.id, 7);
+    assert_eq!(book.title, "Dune");
+    assert_eq!(book.language, "english");
+    assert_eq!(book.year, 1965);
+
+    let row = HashMap::from([("id", "x")]);
+    assert!(mapping
+        .map(false, |column| row.get(column).copied())
+        .is_err());
+}
+
+#[test]
+fn test_map_by_index() {
+    let mapping: FieldMapping = toml::from_str(
+        r#"
+        [fields.md5]
+        column = "2"
+        transforms = ["uppercase"]
+        "#,
+    )
+    .unwrap();
+
+    let row = ["3", "Emma", "abc"];
+    let book = mapping
+        .map(true, |column| {
+            row.get(column.parse::<usize>().ok()?).copied()
+        })
+        .unwrap();
+    assert_eq!(book.id, 3);
+    assert_eq!(book.title, "Emma");
+    // fields not listed keep reading their own position
+    assert_eq!(book.author, "abc");
+    assert_eq!(book.md5, "ABC");
+}
+
+#[test]
+fn test_unknown_field() {
+    let path = crate::source::temp_file("mapping.toml", "[fields.subtitle]\ncolumn = \"sub\"\n");
+    let result = FieldMapping::from_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(Error::Mapping(_))));
+}
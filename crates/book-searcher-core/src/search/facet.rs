@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+use tantivy::aggregation::{
+    agg_req::Aggregations,
+    agg_result::{AggregationResult, AggregationResults, BucketEntries, BucketResult},
+    AggregationCollector, AggregationLimits, Key,
+};
+
+/// Maximum number of values returned for each term facet.
+const TERMS_FACET_SIZE: u32 = 50;
+/// Width of the year buckets.
+const YEAR_FACET_INTERVAL: u64 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum FacetField {
+    Language,
+    Extension,
+    Year,
+}
+
+impl FacetField {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Language => "language",
+            Self::Extension => "extension",
+            Self::Year => "year",
+        }
+    }
+}
+
+impl fmt::Display for FacetField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for FacetField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "language" => Ok(Self::Language),
+            "extension" => Ok(Self::Extension),
+            "year" => Ok(Self::Year),
+            _ => Err(format!(
+                "facet not valid: {:?}",
+                ["language", "extension", "year"]
+            )),
+        }
+    }
+}
+
+/// Number of matched documents for one facet value.
+///
+/// For the year facet, `value` is the first year of the bucket.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FacetCount {
+    pub value: String,
+    pub count: u64,
+}
+
+pub type Facets = BTreeMap<FacetField, Vec<FacetCount>>;
+
+pub(crate) fn facet_collector(fields: &[FacetField]) -> AggregationCollector {
+    let aggs = fields
+        .iter()
+        .map(|field| {
+            let agg = match field {
+                FacetField::Language | FacetField::Extension => serde_json::json!({
+                    "terms": { "field": field.name(), "size": TERMS_FACET_SIZE }
+                }),
+                FacetField::Year => serde_json::json!({
+                    "histogram": {
+                        "field": field.name(),
+                        "interval": YEAR_FACET_INTERVAL,
+                        "min_doc_count": 1
+                    }
+                }),
+            };
+            (field.name().to_owned(), agg)
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    let aggs: Aggregations =
+        serde_json::from_value(aggs.into()).expect("facet aggregation request");
+    AggregationCollector::from_aggs(aggs, AggregationLimits::default())
+}
+
+pub(crate) fn facets_from_aggregation(
+    fields: &[FacetField],
+    mut results: AggregationResults,
+) -> Facets {
+    fields
+        .iter()
+        .filter_map(|field| {
+            let result = results.0.remove(field.name())?;
+            let counts = match result {
+                AggregationResult::BucketResult(BucketResult::Terms { buckets, .. }) => buckets
+                    .into_iter()
+                    .filter(|bucket| !key_to_string(&bucket.key).is_empty())
+                    .map(|bucket| FacetCount {
+                        value: key_to_string(&bucket.key),
+                        count: bucket.doc_count,
+                    })
+                    .collect(),
+                AggregationResult::BucketResult(BucketResult::Histogram {
+                    buckets: BucketEntries::Vec(buckets),
+                }) => buckets
+                    .into_iter()
+                    // year 0 means unknown
+                    .filter(|bucket| bucket.key != Key::F64(0.0))
+                    .map(|bucket| FacetCount {
+                        value: key_to_string(&bucket.key),
+                        count: bucket.doc_count,
+                    })
+                    .collect(),
+                _ => return None,
+            };
+            Some((*field, counts))
+        })
+        .collect()
+}
+
+/// Sum up the facet counts of several indexes.
+pub(crate) fn merge_facets(all_facets: Vec<Facets>) -> Facets {
+    let mut merged: BTreeMap<FacetField, BTreeMap<String, u64>> = BTreeMap::new();
+    for facets in all_facets {
+        for (field, counts) in facets {
+            let values = merged.entry(field).or_default();
+            for FacetCount { value, count } in counts {
+                *values.entry(value).or_default() += count;
+            }
+        }
+    }
+
+    merged
+        .into_iter()
+        .map(|(field, values)| {
+            let mut counts: Vec<FacetCount> = values
+                .into_iter()
+                .map(|(value, count)| FacetCount { value, count })
+                .collect();
+            match field {
+                FacetField::Year => counts.sort_by_key(|c| c.value.parse::<u64>().unwrap_or(0)),
+                FacetField::Language | FacetField::Extension => {
+                    counts.sort_by(|a, b| b.count.cmp(&a.count));
+                    counts.truncate(TERMS_FACET_SIZE as usize);
+                }
+            }
+            (field, counts)
+        })
+        .collect()
+}
+
+fn key_to_string(key: &Key) -> String {
+    match key {
+        Key::Str(s) => s.to_owned(),
+        Key::F64(f) => (*f as u64).to_string(),
+    }
+}
+
+#[cfg(test)]
+fn counts(facets: &Facets, field: FacetField) -> Vec<(&str, u64)> {
+    facets[&field]
+        .iter()
+        .map(|c| (c.value.as_str(), c.count))
+        .collect()
+}
+
+#[test]
+fn test_facet_field_from_str() {
+    assert_eq!(" year".parse(), Ok(FacetField::Year));
+    assert_eq!("language".parse(), Ok(FacetField::Language));
+    assert!("author".parse::<FacetField>().is_err());
+}
+
+#[test]
+fn test_facets() {
+    use crate::{search::SearchQuery, Book, Searcher};
+
+    let books = [
+        ("english", "epub", 1965),
+        ("english", "pdf", 1961),
+        ("french", "epub", 1857),
+        ("", "epub", 0),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(id, (language, extension, year))| Book {
+        language: language.to_owned(),
+        extension: extension.to_owned(),
+        year,
+        ..crate::test_book(id as u64, "book")
+    })
+    .collect();
+    let searcher = Searcher::with_books(books);
+
+    let query = SearchQuery {
+        query: Some("book".to_owned()),
+        facets: vec![
+            FacetField::Language,
+            FacetField::Extension,
+            FacetField::Year,
+        ],
+        ..Default::default()
+    };
+    let facets = searcher.search(&query, 0, 0).unwrap().facets;
+    // empty languages and unknown years are left out
+    assert_eq!(
+        counts(&facets, FacetField::Language),
+        [("english", 2), ("french", 1)]
+    );
+    assert_eq!(
+        counts(&facets, FacetField::Extension),
+        [("epub", 3), ("pdf", 1)]
+    );
+    assert_eq!(
+        counts(&facets, FacetField::Year),
+        [("1850", 1), ("1960", 2)]
+    );
+}
+
+#[test]
+fn test_merge_facets() {
+    let facets = |counts: &[(FacetField, &str, u64)]| {
+        let mut facets = Facets::new();
+        for &(field, value, count) in counts {
+            facets.entry(field).or_default().push(FacetCount {
+                value: value.to_owned(),
+                count,
+            });
+        }
+        facets
+    };
+
+    let merged = merge_facets(vec![
+        facets(&[
+            (FacetField::Extension, "pdf", 2),
+            (FacetField::Extension, "epub", 1),
+            (FacetField::Year, "1990", 4),
+        ]),
+        facets(&[
+            (FacetField::Extension, "epub", 5),
+            (FacetField::Year, "2000", 1),
+            (FacetField::Year, "980", 1),
+        ]),
+    ]);
+    // terms by count, years in order
+    assert_eq!(
+        counts(&merged, FacetField::Extension),
+        [("epub", 6), ("pdf", 2)]
+    );
+    assert_eq!(
+        counts(&merged, FacetField::Year),
+        [("980", 1), ("1990", 4), ("2000", 1)]
+    );
+    assert!(!merged.contains_key(&FacetField::Language));
+}
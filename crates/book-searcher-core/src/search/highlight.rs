@@ -0,0 +1,185 @@
+use crate::{Result, Searcher};
+use serde::Serialize;
+use std::ops::Range;
+use tantivy::{query::Query, schema::Field, Document, SnippetGenerator};
+
+const HIGHLIGHT_PREFIX: &str = "<b>";
+const HIGHLIGHT_POSTFIX: &str = "</b>";
+
+/// Fields with matched words wrapped in `<b>` tags, html escaped.
+#[derive(Debug, Default, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Highlight {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+}
+
+pub(crate) struct Highlighter {
+    title: (Field, SnippetGenerator),
+    author: (Field, SnippetGenerator),
+    publisher: (Field, SnippetGenerator),
+}
+
+impl Highlighter {
+    pub fn new(
+        searcher: &Searcher,
+        index_searcher: &tantivy::Searcher,
+        query: &dyn Query,
+    ) -> Result<Self> {
+        let generator = |field: Field| -> Result<(Field, SnippetGenerator)> {
+            let mut generator = SnippetGenerator::create(index_searcher, query, field)?;
+            // highlight the whole field, never cut it into fragments
+            generator.set_max_num_chars(usize::MAX);
+            Ok((field, generator))
+        };
+
+        Ok(Self {
+            title: generator(searcher.title)?,
+            author: generator(searcher.author)?,
+            publisher: generator(searcher.publisher)?,
+        })
+    }
+
+    pub fn highlight(&self, doc: &Document) -> Highlight {
+        let highlight_field = |(field, generator): &(Field, SnippetGenerator)| {
+            let text = doc.get_first(*field)?.as_text()?;
+            let snippet = generator.snippet(text);
+            if snippet.is_empty() {
+                return None;
+            }
+            // the only fragment starts at 0, so ranges are offsets in text
+            Some(to_html(text, snippet.highlighted()))
+        };
+
+        Highlight {
+            title: highlight_field(&self.title),
+            author: highlight_field(&self.author),
+            publisher: highlight_field(&self.publisher),
+        }
+    }
+}
+
+/// Wrap ranges of `text` in highlight tags.
+///
+/// Tokens like pinyin share offsets with the words they come from, so ranges
+/// may be unordered and overlapped.
+fn to_html(text: &str, ranges: &[Range<usize>]) -> String {
+    let mut ranges = ranges
+        .iter()
+        .filter(|range| {
+            range.start < range.end
+                && range.end <= text.len()
+                && text.is_char_boundary(range.start)
+                && text.is_char_boundary(range.end)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    ranges.sort_unstable_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    let mut html = String::with_capacity(text.len() + merged.len() * 7);
+    let mut start_from = 0;
+    for range in merged {
+        html.push_str(&escape_html(&text[start_from..range.start]));
+        html.push_str(HIGHLIGHT_PREFIX);
+        html.push_str(&escape_html(&text[range.clone()]));
+        html.push_str(HIGHLIGHT_POSTFIX);
+        start_from = range.end;
+    }
+    html.push_str(&escape_html(&text[start_from..]));
+    html
+}
+
+/// Escape the characters with a special meaning in html and xml.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[test]
+fn test_to_html() {
+    assert_eq!(
+        to_html("三体 <I>", &[3..6, 0..6, 8..9, 0..3]),
+        "<b>三体</b> &lt;<b>I</b>&gt;"
+    );
+}
+
+#[test]
+fn test_highlight_search() {
+    use crate::{search::SearchQuery, test_book, Book};
+
+    let searcher = Searcher::with_books(vec![
+        test_book(1, "三体：黑暗森林"),
+        Book {
+            author: "Eric Matthes".to_owned(),
+            ..test_book(2, "Python编程：从入门到实践 <2nd>")
+        },
+    ]);
+    let highlight = |query: SearchQuery| {
+        let query = SearchQuery {
+            highlight: true,
+            ..query
+        };
+        let result = searcher.search(&query, 10, 0).unwrap();
+        result
+            .books
+            .into_iter()
+            .map(|hit| {
+                let highlight = hit.highlight.unwrap();
+                (hit.book.id, highlight.title, highlight.author)
+            })
+            .collect::<Vec<_>>()
+    };
+    let raw = |text: &str| SearchQuery {
+        query: Some(text.to_owned()),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        highlight(raw("黑暗森林")),
+        [(1, Some("三体：<b>黑暗森林</b>".to_owned()), None)]
+    );
+    // adjacent words of both scripts are one highlight
+    assert_eq!(
+        highlight(raw("编程 python")),
+        [(
+            2,
+            Some("<b>Python编程</b>：从入门到实践 &lt;2nd&gt;".to_owned()),
+            None
+        )]
+    );
+    let fields = SearchQuery {
+        title: Some("入门".to_owned()),
+        author: Some("matthes".to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(
+        highlight(fields),
+        [(
+            2,
+            Some("Python编程：从<b>入门</b>到实践 &lt;2nd&gt;".to_owned()),
+            Some("Eric <b>Matthes</b>".to_owned())
+        )]
+    );
+}
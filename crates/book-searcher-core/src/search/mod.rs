@@ -1,54 +1,268 @@
-use crate::{Book, Searcher};
-pub use query::SearchQuery;
+use crate::{Book, Result, Searcher};
+pub use facet::{FacetCount, FacetField, Facets};
+use highlight::Highlighter;
+pub use highlight::{escape_html, Highlight};
+pub use query::{SearchMode, SearchQuery, SortBy, SortOrder};
+use serde::Serialize;
+pub use suggest::SuggestField;
 use tantivy::{
     collector::{Count, TopDocs},
-    DocId, Score, SegmentReader,
+    query::Query,
+    DocAddress, DocId, Order, Score, SegmentReader,
 };
 
+pub(crate) mod facet;
+mod highlight;
 mod query;
+mod suggest;
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub book: Book,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<Highlight>,
+    /// Name of the index the book comes from, set when merging results of several indexes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+    /// Relevance score, zero when sorted by a field.
+    #[serde(skip)]
+    pub score: Score,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SearchResult {
+    pub total: usize,
+    pub books: Vec<SearchHit>,
+    /// Counts of matched books per facet value, keyed by facet field.
+    #[serde(skip_serializing_if = "Facets::is_empty")]
+    #[cfg_attr(feature = "openapi", schema(value_type = Object))]
+    pub facets: Facets,
+}
+
+impl SearchResult {
+    /// Merge the results of searching several named indexes with the same query.
+    ///
+    /// Each index must be searched with `offset + limit` as limit and no offset.
+    /// Relevance scores are normalised by the best score of each index before merging.
+    pub fn merge(
+        results: Vec<(String, SearchResult)>,
+        query: &SearchQuery,
+        limit: usize,
+        offset: usize,
+    ) -> SearchResult {
+        let mut total = 0;
+        let mut books = Vec::new();
+        let mut facets = Vec::new();
+        for (name, result) in results {
+            total += result.total;
+            facets.push(result.facets);
+
+            let max_score = result
+                .books
+                .iter()
+                .map(|hit| hit.score)
+                .fold(0.0, Score::max);
+            books.extend(result.books.into_iter().map(|mut hit| {
+                if max_score > 0.0 {
+                    hit.score /= max_score;
+                }
+                hit.index = Some(name.clone());
+                hit
+            }));
+        }
+
+        if query.sort == SortBy::Relevance {
+            books.sort_by(|a, b| b.score.total_cmp(&a.score));
+        } else {
+            books.sort_by(|a, b| {
+                let ordering = query
+                    .sort
+                    .book_value(&a.book)
+                    .cmp(&query.sort.book_value(&b.book));
+                match query.order {
+                    SortOrder::Asc => ordering,
+                    SortOrder::Desc => ordering.reverse(),
+                }
+            });
+        }
+
+        SearchResult {
+            total,
+            books: books.into_iter().skip(offset).take(limit).collect(),
+            facets: facet::merge_facets(facets),
+        }
+    }
+}
 
 impl Searcher {
-    pub fn search(&self, query: &SearchQuery, limit: usize, offset: usize) -> (Vec<Book>, usize) {
-        let Ok(reader) = self.index.reader() else {
-            return (vec![], 0);
+    pub fn search(&self, query: &SearchQuery, limit: usize, offset: usize) -> Result<SearchResult> {
+        let sort = query.sort;
+        let order = match query.order {
+            SortOrder::Asc => Order::Asc,
+            SortOrder::Desc => Order::Desc,
         };
+        let facet_fields = &query.facets;
+        let highlight = query.highlight;
+        let query = query.parse(self)?;
 
-        let Ok(query) = query.parse(self) else {
-            return (vec![], 0);
+        let searcher = self.reader.searcher();
+        let (count, facets) = if facet_fields.is_empty() {
+            (searcher.search(&query, &Count)?, Facets::new())
+        } else {
+            let collector = (Count, facet::facet_collector(facet_fields));
+            let (count, aggregation) = searcher.search(&query, &collector)?;
+            (
+                count,
+                facet::facets_from_aggregation(facet_fields, aggregation),
+            )
         };
 
-        let searcher = reader.searcher();
-        let count = searcher.search(&query, &Count).unwrap();
-
-        let top_docs_by_custom_score = TopDocs::with_limit(limit).and_offset(offset).tweak_score(
-            move |segment_reader: &SegmentReader| {
-                let score_boost = segment_reader
-                    .fast_fields()
-                    .u64("score_boost")
-                    .unwrap()
-                    .first_or_default_col(1);
-
-                move |doc: DocId, original_score: Score| {
-                    let score_boost: u64 = score_boost.get_val(doc);
-                    let score_boost = ((10 + score_boost) as Score).log10();
-                    original_score * score_boost
-                }
-            },
-        );
+        if let Some(hook) = &self.hook {
+            hook.searched(count);
+        }
 
-        let Ok(top_docs) = searcher.search(&query, &top_docs_by_custom_score) else {
-            return (vec![], count);
+        // tantivy panics with zero limit, e.g. when only facets are requested
+        let doc_addresses: Vec<(Score, DocAddress)> = if limit == 0 {
+            vec![]
+        } else if let Some(field) = sort.field_name() {
+            let top_docs_by_field = TopDocs::with_limit(limit)
+                .and_offset(offset)
+                .order_by_fast_field::<u64>(field, order);
+            let top_docs = searcher.search(&query, &top_docs_by_field)?;
+            top_docs.into_iter().map(|(_, doc)| (0.0, doc)).collect()
+        } else {
+            top_docs_by_relevance(&searcher, &query, limit, offset)?
         };
 
-        let books = top_docs
-            .iter()
-            .map(|d| {
-                let doc = searcher.doc(d.1).unwrap();
-                let item: Book = (&self.schema, doc).into();
-                item
+        let highlighter = if highlight {
+            Some(Highlighter::new(self, &searcher, &query)?)
+        } else {
+            None
+        };
+
+        let books = doc_addresses
+            .into_iter()
+            .map(|(score, doc_address)| {
+                let doc = searcher.doc(doc_address)?;
+                let highlight = highlighter.as_ref().map(|h| h.highlight(&doc));
+                let book: Book = (&self.schema, doc).into();
+                Ok(SearchHit {
+                    book,
+                    highlight,
+                    index: None,
+                    score,
+                })
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
-        (books, count)
+        Ok(SearchResult {
+            total: count,
+            books,
+            facets,
+        })
     }
 }
+
+fn top_docs_by_relevance(
+    searcher: &tantivy::Searcher,
+    query: &dyn Query,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<(Score, DocAddress)>> {
+    let top_docs_by_custom_score = TopDocs::with_limit(limit).and_offset(offset).tweak_score(
+        move |segment_reader: &SegmentReader| {
+            let score_boost = segment_reader
+                .fast_fields()
+                .u64("score_boost")
+                .unwrap()
+                .first_or_default_col(1);
+
+            move |doc: DocId, original_score: Score| {
+                let score_boost: u64 = score_boost.get_val(doc);
+                let score_boost = ((10 + score_boost) as Score).log10();
+                original_score * score_boost
+            }
+        },
+    );
+
+    Ok(searcher.search(query, &top_docs_by_custom_score)?)
+}
+
+#[test]
+fn test_merge() {
+    let result = |hits: &[(u64, u64, Score)], facets: &[(&str, u64)]| SearchResult {
+        total: hits.len(),
+        books: hits
+            .iter()
+            .map(|&(id, year, score)| SearchHit {
+                book: Book {
+                    id,
+                    year,
+                    ..Default::default()
+                },
+                highlight: None,
+                index: None,
+                score,
+            })
+            .collect(),
+        facets: Facets::from([(
+            FacetField::Language,
+            facets
+                .iter()
+                .map(|&(value, count)| FacetCount {
+                    value: value.to_owned(),
+                    count,
+                })
+                .collect(),
+        )]),
+    };
+    let results = || {
+        vec![
+            (
+                "zh".to_owned(),
+                result(&[(1, 2001, 10.0), (2, 1999, 5.0)], &[("chinese", 2)]),
+            ),
+            (
+                "en".to_owned(),
+                result(
+                    &[(3, 2010, 2.0), (4, 1990, 1.5), (5, 2005, 0.5)],
+                    &[("english", 3), ("chinese", 1)],
+                ),
+            ),
+        ]
+    };
+    let ids = |result: &SearchResult| {
+        result
+            .books
+            .iter()
+            .map(|hit| (hit.book.id, hit.index.clone().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    // scores are normalised per index
+    let merged = SearchResult::merge(results(), &SearchQuery::default(), 3, 1);
+    assert_eq!(merged.total, 5);
+    assert_eq!(
+        ids(&merged),
+        [
+            (3, "en".to_owned()),
+            (4, "en".to_owned()),
+            (2, "zh".to_owned())
+        ]
+    );
+    let languages = &merged.facets[&FacetField::Language];
+    assert_eq!(languages[0].value, "chinese");
+    assert_eq!(languages[0].count, 3);
+    assert_eq!(languages[1].value, "english");
+
+    let query = SearchQuery {
+        sort: SortBy::Year,
+        order: SortOrder::Asc,
+        ..Default::default()
+    };
+    let merged = SearchResult::merge(results(), &query, 2, 0);
+    assert_eq!(ids(&merged), [(4, "en".to_owned()), (2, "zh".to_owned())]);
+}
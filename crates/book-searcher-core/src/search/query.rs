@@ -1,17 +1,38 @@
-use crate::Searcher;
+use super::FacetField;
+use crate::{Book, Searcher, EXACT_BOOST};
 use serde::Deserialize;
+use serde_with::{
+    formats::CommaSeparator, serde_as, DisplayFromStr, PickFirst, StringWithSeparator,
+};
+use std::ops::Bound;
 use tantivy::{
-    query::{BooleanQuery, BoostQuery, Occur, PhraseQuery, Query, QueryParserError, TermQuery},
+    query::{
+        BooleanQuery, BoostQuery, FuzzyTermQuery, Occur, PhraseQuery, Query, QueryParserError,
+        RangeQuery, TermQuery,
+    },
     schema::{Field, IndexRecordOption},
     tokenizer::TextAnalyzer,
     Term,
 };
+use tantivy_meta_tokenizer::utils;
+#[cfg(feature = "pinyin")]
+use tantivy_meta_tokenizer::{MIN_PINYIN_INITIALS_LEN, PINYIN_INITIALS_PREFIX};
+
+/// Longest latin word also searched as pinyin initials.
+#[cfg(feature = "pinyin")]
+const MAX_INITIALS_LEN: usize = 8;
+/// Pinyin initials are ambiguous, rank them below books matching the word itself.
+#[cfg(feature = "pinyin")]
+const INITIALS_BOOST: f32 = 0.5;
 
 #[derive(Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum SearchMode {
     Filter,
     Explore,
+    /// Like filter, but also match misspelled latin words
+    Fuzzy,
 }
 
 impl Default for SearchMode {
@@ -20,7 +41,58 @@ impl Default for SearchMode {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Relevance,
+    Year,
+    Filesize,
+    Pages,
+    Id,
+}
+
+impl SortBy {
+    /// Name of the fast field to sort by, `None` for relevance.
+    pub(crate) fn field_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Relevance => None,
+            Self::Year => Some("year"),
+            Self::Filesize => Some("filesize"),
+            Self::Pages => Some("pages"),
+            Self::Id => Some("id"),
+        }
+    }
+
+    /// Value of the sort field of a book, `None` for relevance.
+    pub(crate) fn book_value(&self, book: &Book) -> Option<u64> {
+        match self {
+            Self::Relevance => None,
+            Self::Year => Some(book.year),
+            Self::Filesize => Some(book.filesize),
+            Self::Pages => Some(book.pages),
+            Self::Id => Some(book.id),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[serde_as]
 #[derive(Debug, Default, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
 pub struct SearchQuery {
     pub title: Option<String>,
     pub author: Option<String>,
@@ -30,9 +102,44 @@ pub struct SearchQuery {
     pub isbn: Option<String>,
     pub id: Option<String>,
 
+    // numbers may come as strings from flattened url queries
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    #[serde(default)]
+    pub year_from: Option<u64>,
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    #[serde(default)]
+    pub year_to: Option<u64>,
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    #[serde(default)]
+    pub pages_min: Option<u64>,
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    #[serde(default)]
+    pub pages_max: Option<u64>,
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    #[serde(default)]
+    pub filesize_min: Option<u64>,
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    #[serde(default)]
+    pub filesize_max: Option<u64>,
+
     pub query: Option<String>,
     #[serde(default)]
     pub mode: SearchMode,
+    #[serde(default)]
+    pub sort: SortBy,
+    #[serde(default)]
+    pub order: SortOrder,
+
+    /// Count matched documents per value of these fields, e.g. `language,extension,year`.
+    #[serde_as(as = "StringWithSeparator::<CommaSeparator, FacetField>")]
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi", param(value_type = Option<String>))]
+    pub facets: Vec<FacetField>,
+
+    /// Return title, author and publisher with matched words highlighted.
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    #[serde(default)]
+    pub highlight: bool,
 }
 
 impl SearchQuery {
@@ -44,26 +151,69 @@ impl SearchQuery {
     }
 
     pub fn parse(&self, searcher: &Searcher) -> Result<Box<dyn Query>, QueryParserError> {
+        let query = self.parse_text(searcher)?;
+
+        let ranges = self.range_queries();
+        if ranges.is_empty() {
+            return query.ok_or(QueryParserError::AllButQueryForbidden);
+        }
+
+        // range filters always need to be met, whatever the search mode is
+        let queries = query.into_iter().chain(ranges).collect();
+        Ok(new_bool_query(queries, SearchMode::Filter).unwrap())
+    }
+
+    fn range_queries(&self) -> Vec<Box<dyn Query>> {
+        [
+            ("year", self.year_from, self.year_to),
+            ("pages", self.pages_min, self.pages_max),
+            ("filesize", self.filesize_min, self.filesize_max),
+        ]
+        .into_iter()
+        .filter(|(_, min, max)| min.is_some() || max.is_some())
+        .map(|(field, min, max)| {
+            let lower = min.map_or(Bound::Unbounded, Bound::Included);
+            let upper = max.map_or(Bound::Unbounded, Bound::Included);
+            Box::new(RangeQuery::new_u64_bounds(field.to_owned(), lower, upper)) as Box<dyn Query>
+        })
+        .collect()
+    }
+
+    fn parse_text(&self, searcher: &Searcher) -> Result<Option<Box<dyn Query>>, QueryParserError> {
         // If query is specified, use QueryParser to parse
         if let Some(ref raw_query) = self.query {
-            return match self.mode {
+            let language = self.language.as_deref();
+            let query = match self.mode {
                 SearchMode::Filter => {
-                    let mut query_parser = searcher.query_parser.clone();
+                    let mut query_parser = searcher.query_parser(language);
                     query_parser.set_conjunction_by_default();
-                    query_parser.parse_query(raw_query)
+                    Some(query_parser.parse_query(raw_query)?)
                 }
-                SearchMode::Explore => searcher.query_parser.parse_query(raw_query),
+                SearchMode::Explore => {
+                    Some(searcher.query_parser(language).parse_query(raw_query)?)
+                }
+                // query syntax is not supported, search the text in title, author and publisher
+                SearchMode::Fuzzy => fuzzy_text_query(searcher, raw_query, language),
             };
+            return Ok(with_initials(
+                searcher,
+                query,
+                &[searcher.title, searcher.author],
+                raw_query,
+            ));
         }
 
         // else construct Query
         let mut queries: Vec<Box<dyn Query>> = Vec::with_capacity(4);
 
         let mut tokenizer = searcher.tokenizer.clone();
+        let mut latin = searcher.latin_tokenizer(self.language.as_deref());
 
         if let Some(ref title) = self.title {
             let terms = get_positions_and_terms(searcher.title, title, &mut tokenizer);
-            if let Some(query) = phrase_or_term_query(terms) {
+            let stem_terms = get_positions_and_terms(searcher.title_stem, title, &mut latin);
+            let query = self.exact_or_stemmed_query(terms, stem_terms);
+            if let Some(query) = with_initials(searcher, query, &[searcher.title], title) {
                 let query = BoostQuery::new(Box::new(query), 3.0);
                 queries.push(Box::new(query));
             }
@@ -71,7 +221,9 @@ impl SearchQuery {
 
         if let Some(ref author) = self.author {
             let terms = get_positions_and_terms(searcher.author, author, &mut tokenizer);
-            if let Some(query) = phrase_or_term_query(terms) {
+            let stem_terms = get_positions_and_terms(searcher.author_stem, author, &mut latin);
+            let query = self.exact_or_stemmed_query(terms, stem_terms);
+            if let Some(query) = with_initials(searcher, query, &[searcher.author], author) {
                 let query = BoostQuery::new(Box::new(query), 2.0);
                 queries.push(Box::new(query));
             }
@@ -79,7 +231,9 @@ impl SearchQuery {
 
         if let Some(ref publisher) = self.publisher {
             let terms = get_positions_and_terms(searcher.publisher, publisher, &mut tokenizer);
-            if let Some(query) = phrase_or_term_query(terms) {
+            let stem_terms =
+                get_positions_and_terms(searcher.publisher_stem, publisher, &mut latin);
+            if let Some(query) = self.exact_or_stemmed_query(terms, stem_terms) {
                 queries.push(Box::new(query));
             }
         }
@@ -107,25 +261,43 @@ impl SearchQuery {
         }
 
         if let Some(ref isbn) = self.isbn {
-            let term = Term::from_field_text(searcher.isbn, isbn.to_ascii_lowercase().trim());
-            let query = TermQuery::new(term, IndexRecordOption::WithFreqsAndPositions);
-            queries.push(Box::new(query));
+            if let Some(query) = searcher.isbn_query(isbn) {
+                queries.push(Box::new(query));
+            }
         }
 
         if let Some(ref id) = self.id {
-            if let Ok(id) = id.parse() {
-                let term = Term::from_field_u64(searcher.id, id);
-                let query = TermQuery::new(term, IndexRecordOption::Basic);
-                queries.push(Box::new(query));
-            }
+            let id = id.trim().parse()?;
+            let term = Term::from_field_u64(searcher.id, id);
+            let query = TermQuery::new(term, IndexRecordOption::Basic);
+            queries.push(Box::new(query));
         }
 
-        if let Some(query) = new_bool_query(queries, self.mode) {
-            Ok(Box::new(query))
-        } else {
-            Err(QueryParserError::AllButQueryForbidden)
+        Ok(new_bool_query(queries, self.mode))
+    }
+
+    fn text_field_query(&self, terms: Vec<(usize, Term)>) -> Option<Box<dyn Query>> {
+        match self.mode {
+            SearchMode::Fuzzy => fuzzy_terms_query(terms),
+            SearchMode::Filter | SearchMode::Explore => phrase_or_term_query(terms),
         }
     }
+
+    /// Match the text as written or stemmed, exact matches boosted.
+    fn exact_or_stemmed_query(
+        &self,
+        terms: Vec<(usize, Term)>,
+        stem_terms: Vec<(usize, Term)>,
+    ) -> Option<Box<dyn Query>> {
+        let exact = self
+            .text_field_query(terms)
+            .map(|query| Box::new(BoostQuery::new(query, EXACT_BOOST)) as Box<dyn Query>);
+        let stemmed = self.text_field_query(stem_terms);
+        new_bool_query(
+            exact.into_iter().chain(stemmed).collect(),
+            SearchMode::Explore,
+        )
+    }
 }
 
 pub(crate) fn get_positions_and_terms(
@@ -142,7 +314,6 @@ pub(crate) fn get_positions_and_terms(
     positions_and_terms
 }
 
-#[allow(dead_code)]
 pub(crate) fn get_terms(field: Field, value: &str, text_analyzer: &mut TextAnalyzer) -> Vec<Term> {
     let mut terms = Vec::new();
     let mut token_stream = text_analyzer.token_stream(value);
@@ -168,6 +339,154 @@ pub(crate) fn phrase_or_term_query(terms: Vec<(usize, Term)>) -> Option<Box<dyn
     })
 }
 
+/// Allowed edit distance of a latin word, longer words allow more typos.
+fn fuzzy_distance(text: &str) -> u8 {
+    if text.chars().any(|c| !c.is_alphabetic())
+        || utils::is_chinese(text)
+        || utils::is_japanese(text)
+        || utils::is_korean(text)
+    {
+        return 0;
+    }
+    match text.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Exact term boosted above fuzzy matches of the term.
+pub(crate) fn fuzzy_term_query(term: Term) -> Box<dyn Query> {
+    let value = term.value();
+    let distance = value.as_str().map(fuzzy_distance).unwrap_or_default();
+    let exact = Box::new(TermQuery::new(
+        term.clone(),
+        IndexRecordOption::WithFreqsAndPositions,
+    ));
+    if distance == 0 {
+        return exact;
+    }
+
+    let fuzzy = Box::new(FuzzyTermQuery::new(term, distance, true));
+    Box::new(BooleanQuery::new(vec![
+        (Occur::Should, Box::new(BoostQuery::new(exact, 2.0))),
+        (Occur::Should, fuzzy),
+    ]))
+}
+
+pub(crate) fn fuzzy_terms_query(terms: Vec<(usize, Term)>) -> Option<Box<dyn Query>> {
+    let queries = terms
+        .into_iter()
+        .map(|(_, term)| fuzzy_term_query(term))
+        .collect();
+    new_bool_query(queries, SearchMode::Fuzzy)
+}
+
+/// Every word of `text` should match title, author or publisher, allowing typos.
+fn fuzzy_text_query(
+    searcher: &Searcher,
+    text: &str,
+    language: Option<&str>,
+) -> Option<Box<dyn Query>> {
+    let mut tokenizer = searcher.tokenizer.clone();
+    let fields = [
+        (searcher.title, 3.0),
+        (searcher.author, 2.0),
+        (searcher.publisher, 1.0),
+    ];
+
+    let mut latin = searcher.latin_tokenizer(language);
+    let stem_fields = [
+        (searcher.title_stem, 3.0),
+        (searcher.author_stem, 2.0),
+        (searcher.publisher_stem, 1.0),
+    ];
+
+    let queries = get_terms(searcher.title, text, &mut tokenizer)
+        .into_iter()
+        .filter_map(|term| {
+            let value = term.value();
+            let text = value.as_str()?;
+            let mut queries: Vec<Box<dyn Query>> = fields
+                .iter()
+                .map(|&(field, boost)| {
+                    let query = fuzzy_term_query(Term::from_field_text(field, text));
+                    Box::new(BoostQuery::new(query, boost)) as Box<dyn Query>
+                })
+                .collect();
+            // the stem of the word, below exact matches which fuzzy_term_query boosts
+            for stem in get_terms(searcher.title_stem, text, &mut latin) {
+                let value = stem.value();
+                let stem = value.as_str()?;
+                queries.extend(stem_fields.iter().map(|&(field, boost)| {
+                    let term = Term::from_field_text(field, stem);
+                    let query = Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs));
+                    Box::new(BoostQuery::new(query, boost)) as Box<dyn Query>
+                }));
+            }
+            new_bool_query(queries, SearchMode::Explore)
+        })
+        .collect();
+
+    new_bool_query(queries, SearchMode::Fuzzy)
+}
+
+/// Also match a short latin word as pinyin initials in the fields, e.g. `hlm` for 红楼梦,
+/// unless the word itself is found in the fields.
+#[cfg(feature = "pinyin")]
+fn with_initials(
+    searcher: &Searcher,
+    query: Option<Box<dyn Query>>,
+    fields: &[Field],
+    text: &str,
+) -> Option<Box<dyn Query>> {
+    let text = text.trim().to_ascii_lowercase();
+    if !(MIN_PINYIN_INITIALS_LEN..=MAX_INITIALS_LEN).contains(&text.len())
+        || !text.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        return query;
+    }
+
+    let index_searcher = searcher.reader.searcher();
+    let indexed = fields.iter().any(|&field| {
+        let term = Term::from_field_text(field, &text);
+        index_searcher
+            .doc_freq(&term)
+            .map_or(false, |freq| freq > 0)
+    });
+    if indexed {
+        return query;
+    }
+
+    let initials = format!("{PINYIN_INITIALS_PREFIX}{text}");
+    let queries = fields
+        .iter()
+        .map(|&field| {
+            let term = Term::from_field_text(field, &initials);
+            Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs)) as Box<dyn Query>
+        })
+        .collect();
+    let initials_query = new_bool_query(queries, SearchMode::Explore)?;
+    let initials_query = Box::new(BoostQuery::new(initials_query, INITIALS_BOOST));
+    new_bool_query(
+        query
+            .into_iter()
+            .chain([initials_query as Box<dyn Query>])
+            .collect(),
+        SearchMode::Explore,
+    )
+}
+
+#[cfg(not(feature = "pinyin"))]
+fn with_initials(
+    _searcher: &Searcher,
+    query: Option<Box<dyn Query>>,
+    _fields: &[Field],
+    _text: &str,
+) -> Option<Box<dyn Query>> {
+    query
+}
+
 pub(crate) fn new_bool_query(
     queries: Vec<Box<dyn Query>>,
     mode: SearchMode,
@@ -177,7 +496,7 @@ pub(crate) fn new_bool_query(
     }
 
     let query = match mode {
-        SearchMode::Filter => {
+        SearchMode::Filter | SearchMode::Fuzzy => {
             BooleanQuery::new(queries.into_iter().map(|q| (Occur::Must, q)).collect())
         }
         SearchMode::Explore => {
@@ -203,3 +522,232 @@ pub(crate) fn new_terms_query(
 
     new_bool_query(queries, mode)
 }
+
+#[cfg(test)]
+fn test_books() -> Vec<Book> {
+    [
+        (1, "Dune", 1965, 412, 1_000),
+        (2, "Emma", 1815, 474, 2_000),
+        (3, "Ulysses", 1922, 730, 3_000),
+        (4, "Solaris", 1961, 204, 4_000),
+    ]
+    .into_iter()
+    .map(|(id, title, year, pages, filesize)| Book {
+        language: "english".to_owned(),
+        year,
+        pages,
+        filesize,
+        ..crate::test_book(id, title)
+    })
+    .collect()
+}
+
+/// Query of `text` in the query syntax.
+#[cfg(test)]
+fn raw_query(text: &str, mode: SearchMode) -> SearchQuery {
+    SearchQuery {
+        query: Some(text.to_owned()),
+        mode,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_range_queries() {
+    let searcher = Searcher::with_books(test_books());
+    let ids = |query: SearchQuery| {
+        let mut ids = searcher.search_ids(&query);
+        ids.sort();
+        ids
+    };
+
+    assert!(SearchQuery::default().range_queries().is_empty());
+
+    // bounds are inclusive
+    let query = SearchQuery {
+        year_from: Some(1922),
+        year_to: Some(1961),
+        ..Default::default()
+    };
+    assert_eq!(ids(query), [3, 4]);
+
+    let query = SearchQuery {
+        pages_min: Some(412),
+        ..Default::default()
+    };
+    assert_eq!(ids(query), [1, 2, 3]);
+
+    let query = SearchQuery {
+        filesize_max: Some(2_000),
+        year_from: Some(1900),
+        ..Default::default()
+    };
+    assert_eq!(ids(query), [1]);
+
+    // ranges always filter, also in explore mode
+    let query = SearchQuery {
+        year_to: Some(1900),
+        ..raw_query("dune OR emma", SearchMode::Explore)
+    };
+    assert_eq!(ids(query), [2]);
+
+    // the same ranges in the query syntax, `[]` inclusive and `{}` exclusive
+    let raw = |text| ids(raw_query(text, SearchMode::Filter));
+    assert_eq!(raw("year:[1922 TO 1961]"), [3, 4]);
+    assert_eq!(raw("year:{1922 TO 1961]"), [4]);
+    assert_eq!(raw("filesize:{* TO 2000]"), [1, 2]);
+    assert_eq!(raw("pages:[412 TO *}"), [1, 2, 3]);
+    assert_eq!(raw("dune year:[1900 TO 2000]"), [1]);
+    assert!(raw("emma year:[1900 TO 2000]").is_empty());
+}
+
+#[test]
+fn test_sort() {
+    let searcher = Searcher::with_books(test_books());
+    let ids = |sort, order| {
+        let query = SearchQuery {
+            year_from: Some(0),
+            sort,
+            order,
+            ..Default::default()
+        };
+        searcher.search_ids(&query)[1..4].to_vec()
+    };
+
+    assert_eq!(ids(SortBy::Year, SortOrder::Desc), [4, 3, 2]);
+    assert_eq!(ids(SortBy::Year, SortOrder::Asc), [3, 4, 1]);
+    assert_eq!(ids(SortBy::Pages, SortOrder::Desc), [2, 1, 4]);
+    assert_eq!(ids(SortBy::Filesize, SortOrder::Asc), [2, 3, 4]);
+    assert_eq!(ids(SortBy::Id, SortOrder::Desc), [3, 2, 1]);
+
+    let book = &test_books()[0];
+    assert_eq!(SortBy::Relevance.field_name(), None);
+    assert_eq!(SortBy::Relevance.book_value(book), None);
+    assert_eq!(SortBy::Pages.field_name(), Some("pages"));
+    assert_eq!(SortBy::Pages.book_value(book), Some(412));
+}
+
+#[test]
+fn test_fuzzy_distance() {
+    assert_eq!(fuzzy_distance("dun"), 0);
+    assert_eq!(fuzzy_distance("dune"), 1);
+    assert_eq!(fuzzy_distance("solaris"), 1);
+    assert_eq!(fuzzy_distance("ulyssess"), 2);
+    // only latin words allow typos
+    assert_eq!(fuzzy_distance("1984abcd"), 0);
+    assert_eq!(fuzzy_distance("三体三体三体"), 0);
+}
+
+#[test]
+fn test_fuzzy_search() {
+    let searcher = Searcher::with_books(test_books());
+    let ids = |text, mode| searcher.search_ids(&raw_query(text, mode));
+
+    assert_eq!(ids("solarsi", SearchMode::Fuzzy), [4]);
+    assert_eq!(ids("ulysess", SearchMode::Fuzzy), [3]);
+    assert!(ids("solarsi", SearchMode::Filter).is_empty());
+    // short words must match exactly
+    assert!(ids("dun", SearchMode::Fuzzy).is_empty());
+    // query syntax is searched as plain text
+    assert_eq!(ids("(dune", SearchMode::Fuzzy), [1]);
+
+    // the exact word ranks above a word one edit away, whatever the index order
+    let searcher = Searcher::with_books(vec![
+        crate::test_book(5, "Dane"),
+        crate::test_book(6, "Dune"),
+    ]);
+    assert_eq!(
+        searcher.search_ids(&raw_query("dune", SearchMode::Fuzzy)),
+        [6, 5]
+    );
+    assert_eq!(
+        searcher.search_ids(&raw_query("dane", SearchMode::Fuzzy)),
+        [5, 6]
+    );
+}
+
+#[test]
+fn test_invalid_id() {
+    let searcher = Searcher::with_books(test_books());
+    let search = |id: &str| {
+        let query = SearchQuery {
+            id: Some(id.to_owned()),
+            ..Default::default()
+        };
+        searcher.search(&query, 10, 0)
+    };
+
+    assert_eq!(search(" 2 ").unwrap().books[0].book.id, 2);
+    assert!(matches!(search("two"), Err(crate::Error::Query(_))));
+}
+
+#[test]
+fn test_stemmed_search() {
+    let searcher = Searcher::with_books(
+        [
+            (5, "Program", "english"),
+            (6, "Programming", "english"),
+            (7, "Les Chanteuses", "french"),
+        ]
+        .into_iter()
+        .map(|(id, title, language)| Book {
+            language: language.to_owned(),
+            ..crate::test_book(id, title)
+        })
+        .collect(),
+    );
+
+    // the exact title ranks above the stemmed one
+    let title = |title: &str| SearchQuery {
+        title: Some(title.to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(searcher.search_ids(&title("programming")), [6, 5]);
+    assert_eq!(searcher.search_ids(&title("program")), [5, 6]);
+
+    // raw queries are stemmed in the language they filter on
+    let french = |mode| SearchQuery {
+        language: Some("french".to_owned()),
+        ..raw_query("chanteuse", mode)
+    };
+    assert_eq!(searcher.search_ids(&french(SearchMode::Filter)), [7]);
+    assert_eq!(searcher.search_ids(&french(SearchMode::Explore)), [7]);
+    assert_eq!(searcher.search_ids(&french(SearchMode::Fuzzy)), [7]);
+    let english = raw_query("chanteuse", SearchMode::Filter);
+    assert!(searcher.search_ids(&english).is_empty());
+}
+
+#[cfg(feature = "pinyin")]
+#[test]
+fn test_initials_search() {
+    let books = || {
+        vec![
+            crate::test_book(1, "红楼梦"),
+            crate::test_book(2, "三体 黑暗森林"),
+        ]
+    };
+    let title = |title: &str| SearchQuery {
+        title: Some(title.to_owned()),
+        ..Default::default()
+    };
+
+    let searcher = Searcher::with_books(books());
+    assert_eq!(searcher.search_ids(&title("hlm")), [1]);
+    assert_eq!(searcher.search_ids(&title("HLM")), [1]);
+    assert_eq!(searcher.search_ids(&title("sthasl")), [2]);
+    for mode in [SearchMode::Filter, SearchMode::Explore, SearchMode::Fuzzy] {
+        assert_eq!(searcher.search_ids(&raw_query("hlm", mode)), [1]);
+    }
+    // too short to be initials
+    assert!(searcher.search_ids(&title("st")).is_empty());
+
+    // a word found in the index is not searched as initials
+    let mut books = books();
+    books.push(crate::test_book(3, "HLM Handbook"));
+    let searcher = Searcher::with_books(books);
+    assert_eq!(searcher.search_ids(&title("hlm")), [3]);
+    assert_eq!(
+        searcher.search_ids(&raw_query("hlm", SearchMode::Explore)),
+        [3]
+    );
+}
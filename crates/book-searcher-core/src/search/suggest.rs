@@ -0,0 +1,176 @@
+use crate::{Result, Searcher};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use tantivy::{
+    collector::TopDocs,
+    query::{BooleanQuery, Occur, Query, TermQuery},
+    schema::IndexRecordOption,
+    Order, Term,
+};
+
+/// Maximum number of completed words used to find suggestions.
+const MAX_EXPANSIONS: usize = 32;
+/// Maximum number of suggestions returned at once.
+const MAX_SUGGESTIONS: usize = 100;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestField {
+    #[default]
+    Title,
+    Author,
+}
+
+impl Searcher {
+    /// Suggest distinct titles or authors starting with `prefix`, better books first.
+    ///
+    /// The last word of `prefix` is completed with the most frequent words of the
+    /// term dictionary, the other words must match exactly.
+    pub fn suggest(&self, prefix: &str, field: SuggestField, limit: usize) -> Result<Vec<String>> {
+        let field = match field {
+            SuggestField::Title => self.title,
+            SuggestField::Author => self.author,
+        };
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+        let limit = limit.min(MAX_SUGGESTIONS);
+
+        let mut tokens = vec![];
+        let mut tokenizer = self.tokenizer.clone();
+        let mut token_stream = tokenizer.token_stream(prefix);
+        token_stream.process(&mut |token| tokens.push(token.clone()));
+
+        // the longest word at the end of prefix is the one to be completed
+        let Some(last) = tokens
+            .iter()
+            .filter(|token| token.offset_to == prefix.trim_end().len())
+            .min_by_key(|token| token.offset_from)
+            .cloned()
+        else {
+            return Ok(vec![]);
+        };
+
+        let searcher = self.reader.searcher();
+
+        // complete the last word with the term dictionary
+        let mut doc_freqs: HashMap<Vec<u8>, u32> = HashMap::new();
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(field)?;
+            let mut stream = inverted_index
+                .terms()
+                .range()
+                .ge(last.text.as_bytes())
+                .into_stream()?;
+            while stream.advance() && stream.key().starts_with(last.text.as_bytes()) {
+                *doc_freqs.entry(stream.key().to_vec()).or_default() += stream.value().doc_freq;
+            }
+        }
+        let mut completions = doc_freqs.into_iter().collect::<Vec<_>>();
+        completions.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+
+        let completion_queries = completions
+            .into_iter()
+            .take(MAX_EXPANSIONS)
+            .filter_map(|(word, _)| {
+                let word = String::from_utf8(word).ok()?;
+                let term = Term::from_field_text(field, &word);
+                let query = TermQuery::new(term, IndexRecordOption::Basic);
+                Some((Occur::Should, Box::new(query) as Box<dyn Query>))
+            })
+            .collect::<Vec<_>>();
+        if completion_queries.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut queries = tokens
+            .iter()
+            .filter(|token| token.offset_to <= last.offset_from)
+            .map(|token| {
+                let term = Term::from_field_text(field, &token.text);
+                let query = TermQuery::new(term, IndexRecordOption::Basic);
+                (Occur::Must, Box::new(query) as Box<dyn Query>)
+            })
+            .collect::<Vec<_>>();
+        queries.push((Occur::Must, Box::new(BooleanQuery::new(completion_queries))));
+        let query = BooleanQuery::new(queries);
+
+        // many books share the same title or author, fetch more to dedup
+        let top_docs = TopDocs::with_limit(limit.saturating_mul(5))
+            .order_by_fast_field::<u64>("score_boost", Order::Desc);
+        let top_docs = searcher.search(&query, &top_docs)?;
+
+        let mut seen = HashSet::new();
+        let mut suggestions = Vec::with_capacity(limit);
+        for (_, doc_address) in top_docs {
+            let doc = searcher.doc(doc_address)?;
+            let Some(text) = doc.get_first(field).and_then(|value| value.as_text()) else {
+                continue;
+            };
+            let text = text.trim();
+            if !text.is_empty() && seen.insert(text.to_lowercase()) {
+                suggestions.push(text.to_owned());
+                if suggestions.len() >= limit {
+                    break;
+                }
+            }
+        }
+
+        Ok(suggestions)
+    }
+}
+
+#[test]
+fn test_suggest() {
+    use crate::Book;
+
+    let searcher = Searcher::new_in_ram().unwrap();
+    let books = [
+        ("Dune", "Frank Herbert"),
+        ("Dune Messiah", "Frank Herbert"),
+        ("dune", "Frank Herbert"),
+        ("Dunes of Egypt", "Anna Dunmore"),
+        ("Solaris", "Stanislaw Lem"),
+        ("三体", "刘慈欣"),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(id, (title, author))| Book {
+        id: id as u64,
+        title: title.to_owned(),
+        author: author.to_owned(),
+        ..Default::default()
+    })
+    .collect();
+    crate::index::index_books(&searcher, books);
+
+    let suggest = |prefix, field, limit| {
+        let mut suggestions = searcher.suggest(prefix, field, limit).unwrap();
+        suggestions.sort();
+        suggestions
+    };
+
+    // titles differing only in case are suggested once
+    assert_eq!(
+        suggest("du", SuggestField::Title, 10),
+        ["Dune", "Dune Messiah", "Dunes of Egypt"]
+    );
+    // words before the last one must match exactly
+    assert_eq!(
+        suggest("dune me", SuggestField::Title, 10),
+        ["Dune Messiah"]
+    );
+    // a trailing space still completes the last word
+    assert_eq!(
+        suggest("dune ", SuggestField::Title, 10),
+        ["Dune", "Dune Messiah", "Dunes of Egypt"]
+    );
+    assert_eq!(suggest("dun", SuggestField::Author, 10), ["Anna Dunmore"]);
+    assert_eq!(suggest("三", SuggestField::Title, 10), ["三体"]);
+    assert_eq!(suggest("du", SuggestField::Title, 1).len(), 1);
+    assert_eq!(suggest("du", SuggestField::Title, usize::MAX).len(), 3);
+    assert!(suggest("du", SuggestField::Title, 0).is_empty());
+    assert!(suggest("xyz", SuggestField::Title, 10).is_empty());
+    assert!(suggest("  ", SuggestField::Title, 10).is_empty());
+}
@@ -0,0 +1,232 @@
+use crate::{Book, FieldMapping, Result};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+pub type Books = Box<dyn Iterator<Item = Result<Book>> + Send>;
+
+/// Format of the raw data file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// CSV without header, columns must be in the order of [`Book`] fields.
+    #[default]
+    Csv,
+    /// CSV with a header row, columns are mapped to [`Book`] fields by name.
+    CsvHeader,
+    /// Tab separated values with a header row, columns are mapped by name.
+    Tsv,
+    /// JSON Lines, one book object per line.
+    Jsonl,
+}
+
+impl InputFormat {
+    fn has_headers(&self) -> bool {
+        matches!(self, Self::CsvHeader | Self::Tsv)
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "csv-header" => Ok(Self::CsvHeader),
+            "tsv" => Ok(Self::Tsv),
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            _ => Err(format!(
+                "input format not valid: {:?}",
+                ["csv", "csv-header", "tsv", "jsonl"]
+            )),
+        }
+    }
+}
+
+/// A raw data file to be indexed.
+#[derive(Clone, Debug)]
+pub struct InputSource {
+    path: PathBuf,
+    format: InputFormat,
+    mapping: Option<Arc<FieldMapping>>,
+}
+
+impl InputSource {
+    pub fn new(path: impl Into<PathBuf>, format: InputFormat) -> Self {
+        Self {
+            path: path.into(),
+            format,
+            mapping: None,
+        }
+    }
+
+    /// Map columns to [`Book`] fields with `mapping` instead of by name or position.
+    pub fn with_mapping(mut self, mapping: Arc<FieldMapping>) -> Self {
+        self.mapping = Some(mapping);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn format(&self) -> InputFormat {
+        self.format
+    }
+
+    /// Number of books in the file, used to show the progress.
+    pub fn count(&self) -> Result<usize> {
+        let lines = BufReader::new(File::open(&self.path)?).lines().count();
+        if self.format.has_headers() {
+            Ok(lines.saturating_sub(1))
+        } else {
+            Ok(lines)
+        }
+    }
+
+    pub fn books(&self) -> Result<Books> {
+        let reader = BufReader::new(File::open(&self.path)?);
+
+        let books: Books = match (self.format, self.mapping.clone()) {
+            (InputFormat::Jsonl, None) => Box::new(reader.lines().filter_map(|line| {
+                Some(match line {
+                    Ok(line) if line.trim().is_empty() => return None,
+                    Ok(line) => serde_json::from_str::<Book>(&line).map_err(Into::into),
+                    Err(err) => Err(err.into()),
+                })
+            })),
+            (InputFormat::Jsonl, Some(mapping)) => {
+                Box::new(reader.lines().filter_map(move |line| {
+                    Some(match line {
+                        Ok(line) if line.trim().is_empty() => return None,
+                        Ok(line) => map_json(&mapping, &line),
+                        Err(err) => Err(err.into()),
+                    })
+                }))
+            }
+            (_, None) => Box::new(
+                self.csv_reader(reader)
+                    .into_deserialize::<Book>()
+                    .map(|result| result.map_err(Into::into)),
+            ),
+            (_, Some(mapping)) => {
+                let mut rdr = self.csv_reader(reader);
+                let headers = if self.format.has_headers() {
+                    Some(rdr.headers()?.clone())
+                } else {
+                    None
+                };
+                Box::new(rdr.into_records().map(move |record| {
+                    let record = record?;
+                    match headers {
+                        Some(ref headers) => mapping.map(false, |column| {
+                            let idx = headers.iter().position(|h| h.trim() == column)?;
+                            record.get(idx)
+                        }),
+                        None => mapping.map(true, |column| record.get(column.parse().ok()?)),
+                    }
+                }))
+            }
+        };
+        Ok(books)
+    }
+
+    fn csv_reader(&self, reader: BufReader<File>) -> csv::Reader<BufReader<File>> {
+        let delimiter = if self.format == InputFormat::Tsv {
+            b'\t'
+        } else {
+            b','
+        };
+        csv::ReaderBuilder::new()
+            .has_headers(self.format.has_headers())
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(reader)
+    }
+}
+
+fn map_json(mapping: &FieldMapping, line: &str) -> Result<Book> {
+    let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(line)?;
+    let values: std::collections::HashMap<&str, String> = object
+        .iter()
+        .filter_map(|(key, value)| match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(s) => Some((key.as_str(), s.to_owned())),
+            value => Some((key.as_str(), value.to_string())),
+        })
+        .collect();
+    mapping.map(false, |column| values.get(column).map(String::as_str))
+}
+
+impl From<PathBuf> for InputSource {
+    fn from(path: PathBuf) -> Self {
+        Self::new(path, InputFormat::default())
+    }
+}
+
+/// Write `content` to a file in the temp directory, named after the test.
+#[cfg(test)]
+pub(crate) fn temp_file(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("book-searcher-{}-{name}", std::process::id()));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn test_input_format_from_str() {
+    assert_eq!("csv".parse(), Ok(InputFormat::Csv));
+    assert_eq!("csv-header".parse(), Ok(InputFormat::CsvHeader));
+    assert_eq!("tsv".parse(), Ok(InputFormat::Tsv));
+    assert_eq!("jsonl".parse(), Ok(InputFormat::Jsonl));
+    assert_eq!("ndjson".parse(), Ok(InputFormat::Jsonl));
+    assert!("xml".parse::<InputFormat>().is_err());
+}
+
+#[test]
+fn test_read_formats() {
+    let read = |name, format, content| {
+        let source = InputSource::new(temp_file(name, content), format);
+        let books = source.books().unwrap().collect::<Result<Vec<_>>>().unwrap();
+        std::fs::remove_file(source.path()).unwrap();
+        books
+    };
+
+    let books = read(
+        "books.csv",
+        InputFormat::Csv,
+        "1,Dune,Frank Herbert,,epub,100,english,1965,412,,,,\n",
+    );
+    assert_eq!(books[0].title, "Dune");
+    assert_eq!(books[0].year, 1965);
+
+    // columns are matched by name, in any order
+    let books = read(
+        "books-header.csv",
+        InputFormat::CsvHeader,
+        "title,id,year\nDune,1,1965\nEmma,2,\n",
+    );
+    assert_eq!(books[1].id, 2);
+    assert_eq!(books[1].title, "Emma");
+    assert_eq!(books[1].year, 0);
+
+    let books = read(
+        "books.tsv",
+        InputFormat::Tsv,
+        "id\ttitle\tauthor\n1\tDune, Messiah\tFrank Herbert\n",
+    );
+    assert_eq!(books[0].title, "Dune, Messiah");
+    assert_eq!(books[0].author, "Frank Herbert");
+
+    // blank lines are skipped, null and missing fields fall back to defaults
+    let books = read(
+        "books.jsonl",
+        InputFormat::Jsonl,
+        "{\"id\":1,\"title\":\"Dune\",\"author\":null}\n\n{\"id\":2,\"title\":\"Emma\",\"year\":\"n/a\"}\n",
+    );
+    assert_eq!(books.len(), 2);
+    assert_eq!(books[0].author, "");
+    assert_eq!(books[1].year, 0);
+}
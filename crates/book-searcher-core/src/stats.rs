@@ -0,0 +1,66 @@
+use crate::{
+    index,
+    search::{facet, FacetCount, FacetField},
+    Compressor, Result, Searcher,
+};
+use serde::Serialize;
+use tantivy::query::AllQuery;
+
+/// Statistics of an index, e.g. for monitoring.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct IndexStats {
+    /// Number of books, deleted books excluded.
+    pub num_docs: u64,
+    pub num_segments: usize,
+    /// Compressor of the document store, e.g. `none`, `lz4` or `zstd`.
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub compressor: Compressor,
+    /// Size in bytes of all files in the index directory.
+    pub size_bytes: u64,
+    /// Schema version the index was built with, `None` before the first commit.
+    pub schema_version: Option<u32>,
+    /// Number of books per language, most frequent first.
+    pub languages: Vec<FacetCount>,
+    /// Number of books per file extension, most frequent first.
+    pub extensions: Vec<FacetCount>,
+}
+
+impl Searcher {
+    pub fn stats(&self) -> Result<IndexStats> {
+        let fields = [FacetField::Language, FacetField::Extension];
+        let collector = facet::facet_collector(&fields);
+        let aggregation = self.reader.searcher().search(&AllQuery, &collector)?;
+        let mut facets = facet::facets_from_aggregation(&fields, aggregation);
+        Ok(IndexStats {
+            num_docs: self.num_docs(),
+            num_segments: self.num_segments(),
+            compressor: self.index.settings().docstore_compression,
+            size_bytes: self.index_size()?,
+            schema_version: index::schema_version(&self.index)?,
+            languages: facets.remove(&FacetField::Language).unwrap_or_default(),
+            extensions: facets.remove(&FacetField::Extension).unwrap_or_default(),
+        })
+    }
+
+    /// Number of books in the index, deleted books excluded.
+    pub fn num_docs(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    pub fn num_segments(&self) -> usize {
+        self.reader.searcher().segment_readers().len()
+    }
+
+    /// Size in bytes of all files in the index directory.
+    pub fn index_size(&self) -> Result<u64> {
+        let mut size = 0;
+        for entry in std::fs::read_dir(&self.index_dir)? {
+            let metadata = entry?.metadata()?;
+            if metadata.is_file() {
+                size += metadata.len();
+            }
+        }
+        Ok(size)
+    }
+}
@@ -3,9 +3,16 @@
     windows_subsystem = "windows"
 )]
 
-use book_searcher_core::{search::SearchQuery, Book, Searcher};
-use log::info;
-use std::{error::Error, path::PathBuf};
+use book_searcher_core::{
+    search::{SearchHit, SearchQuery, SuggestField},
+    Book, FieldMapping, InputSource, Searcher,
+};
+use log::{info, warn};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -56,6 +63,25 @@ impl AppConfig {
     }
 }
 
+/// Opens the index in `index_dir`, or returns `None` if it was built with an
+/// older schema and has to be created again.
+fn open_searcher(index_dir: &Path) -> Result<Option<Searcher>, book_searcher_core::Error> {
+    match Searcher::new(index_dir) {
+        Ok(searcher) => Ok(Some(searcher)),
+        Err(book_searcher_core::Error::OutdatedIndex(dir)) => {
+            warn!("index {dir} is outdated, create the index again");
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn opened(searcher: &Option<Searcher>) -> Result<&Searcher, String> {
+    searcher
+        .as_ref()
+        .ok_or_else(|| "index is outdated, create the index again".to_string())
+}
+
 #[tauri::command]
 async fn get_config(config: State<'_, Mutex<AppConfig>>) -> Result<AppConfig, String> {
     Ok(config.lock().await.clone())
@@ -65,7 +91,7 @@ async fn get_config(config: State<'_, Mutex<AppConfig>>) -> Result<AppConfig, St
 async fn set_config(
     new_config: AppConfig,
     config: State<'_, Mutex<AppConfig>>,
-    searcher: tauri::State<'_, Mutex<Searcher>>,
+    searcher: tauri::State<'_, Mutex<Option<Searcher>>>,
 ) -> Result<(), String> {
     let mut config = config.lock().await;
 
@@ -73,7 +99,7 @@ async fn set_config(
     if config.index_dir != new_config.index_dir {
         info!("index_dir changed, reloading searcher");
         let mut searcher = searcher.lock().await;
-        *searcher = Searcher::new(new_config.index_dir.clone());
+        *searcher = open_searcher(&new_config.index_dir).map_err(|e| e.to_string())?;
     }
 
     *config = new_config;
@@ -85,13 +111,75 @@ async fn set_config(
 
 #[tauri::command]
 async fn search(
-    searcher: tauri::State<'_, Mutex<Searcher>>,
+    searcher: tauri::State<'_, Mutex<Option<Searcher>>>,
     query: SearchQuery,
     limit: usize,
     offset: usize,
-) -> Result<(Vec<Book>, usize), ()> {
+) -> Result<(Vec<SearchHit>, usize), String> {
     info!("Search: {query:?}");
-    Ok(searcher.lock().await.search(&query, limit, offset))
+    opened(&*searcher.lock().await)?
+        .search(&query, limit, offset)
+        .map(|result| (result.books, result.total))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn suggest(
+    searcher: tauri::State<'_, Mutex<Option<Searcher>>>,
+    prefix: String,
+    field: SuggestField,
+    limit: usize,
+) -> Result<Vec<String>, String> {
+    opened(&*searcher.lock().await)?
+        .suggest(&prefix, field, limit)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_book_by_id(
+    searcher: tauri::State<'_, Mutex<Option<Searcher>>>,
+    id: u64,
+) -> Result<Option<Book>, String> {
+    opened(&*searcher.lock().await)?
+        .get_by_id(id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_book_by_md5(
+    searcher: tauri::State<'_, Mutex<Option<Searcher>>>,
+    md5: String,
+) -> Result<Option<Book>, String> {
+    opened(&*searcher.lock().await)?
+        .get_by_md5(&md5)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_books_by_isbn(
+    searcher: tauri::State<'_, Mutex<Option<Searcher>>>,
+    isbn: String,
+) -> Result<Vec<Book>, String> {
+    opened(&*searcher.lock().await)?
+        .get_by_isbn(&isbn)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_book_by_cid(
+    searcher: tauri::State<'_, Mutex<Option<Searcher>>>,
+    cid: String,
+) -> Result<Option<Book>, String> {
+    opened(&*searcher.lock().await)?
+        .get_by_cid(&cid)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn index_outdated(
+    searcher: tauri::State<'_, Mutex<Option<Searcher>>>,
+) -> Result<bool, String> {
+    Ok(searcher.lock().await.is_none())
 }
 
 #[tauri::command]
@@ -103,27 +191,51 @@ fn version() -> String {
 pub struct CreateIndexConfig {
     pub raw_files: Vec<PathBuf>,
     pub compressor: String,
+    #[serde(default)]
+    pub mapping_file: Option<PathBuf>,
 }
 #[tauri::command]
 async fn create_index(
-    searcher: State<'_, Mutex<Searcher>>,
+    searcher: State<'_, Mutex<Option<Searcher>>>,
+    config: State<'_, Mutex<AppConfig>>,
     create_index_config: CreateIndexConfig,
 ) -> Result<(), String> {
     let mut searcher = searcher.lock().await;
+    if searcher.is_none() {
+        // an outdated index can't be updated in place, start over
+        let index_dir = config.lock().await.index_dir.clone();
+        info!("recreating outdated index in {index_dir:?}");
+        std::fs::remove_dir_all(&index_dir).map_err(|e| e.to_string())?;
+        *searcher = Some(Searcher::new(&index_dir).map_err(|e| e.to_string())?);
+    }
+    let searcher = searcher.as_mut().expect("index was just created");
+
     let compressor = if create_index_config.compressor.is_empty() {
         "none"
     } else {
         &create_index_config.compressor
     };
-    searcher.set_compressor(compressor);
+    searcher
+        .set_compressor(compressor)
+        .map_err(|e| e.to_string())?;
+
+    let mapping = match create_index_config.mapping_file {
+        Some(ref mapping_file) => Some(Arc::new(
+            FieldMapping::from_file(mapping_file).map_err(|e| e.to_string())?,
+        )),
+        None => None,
+    };
 
     if create_index_config.raw_files.is_empty() {
         return Err("csv file is missing!".to_string());
     } else {
-        create_index_config
-            .raw_files
-            .iter()
-            .for_each(|file| searcher.index(file));
+        for file in &create_index_config.raw_files {
+            let mut source = InputSource::from(file.clone());
+            if let Some(ref mapping) = mapping {
+                source = source.with_mapping(mapping.clone());
+            }
+            searcher.index(&source).map_err(|e| e.to_string())?;
+        }
     }
     Ok(())
 }
@@ -132,7 +244,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     env_logger::init();
 
     let config = AppConfig::load()?;
-    let searcher = Mutex::new(Searcher::new(&config.index_dir));
+    let searcher = Mutex::new(open_searcher(&config.index_dir)?);
     let config = Mutex::new(config);
 
     info!(
@@ -148,9 +260,15 @@ fn main() -> Result<(), Box<dyn Error>> {
         .invoke_handler(tauri::generate_handler![
             version,
             search,
+            suggest,
+            get_book_by_id,
+            get_book_by_md5,
+            get_books_by_isbn,
+            get_book_by_cid,
             get_config,
             set_config,
-            create_index
+            create_index,
+            index_outdated
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,290 @@
+use anyhow::Context;
+use book_searcher_core::{stemmer_language, LatinOptions};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+const ENV_PREFIX: &str = "BOOK_SEARCHER_";
+
+/// Settings of book-searcher, resolved in layers:
+/// defaults < config file < `BOOK_SEARCHER_*` environment variables < command line.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// webserver bind address
+    pub bind: String,
+    /// directory of the index
+    pub index_dir: PathBuf,
+    /// named index directories served by the webserver, `index_dir` is used when empty
+    pub indexes: BTreeMap<String, PathBuf>,
+    /// number of books returned when a search has no `limit`
+    pub default_limit: usize,
+    /// largest `limit` a search may ask for
+    pub max_limit: usize,
+    /// largest `offset` a search may ask for
+    pub max_offset: usize,
+    /// origins allowed by CORS, `*` allows any origin
+    pub cors_origins: Vec<String>,
+    /// IPFS gateway used for download links in the OPDS feed
+    pub ipfs_gateway: String,
+    /// index the original traditional Chinese words next to the simplified ones
+    pub keep_traditional: bool,
+    /// stemmer of latin text when the language of a book has none, `none` disables stemming
+    pub stem_language: String,
+    /// fold accented latin letters to ascii, so `cafe` matches `café`
+    pub ascii_folding: bool,
+    /// bearer token required by the admin endpoints, they are disabled when unset
+    pub admin_token: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let index_dir = env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join("index")))
+            .unwrap_or_else(|| PathBuf::from("index"));
+
+        Self {
+            bind: "127.0.0.1:7070".to_owned(),
+            index_dir,
+            indexes: BTreeMap::new(),
+            default_limit: 30,
+            max_limit: 100,
+            max_offset: 10_000,
+            cors_origins: vec!["*".to_owned()],
+            ipfs_gateway: "https://ipfs.io".to_owned(),
+            keep_traditional: true,
+            stem_language: "english".to_owned(),
+            ascii_folding: true,
+            admin_token: None,
+        }
+    }
+}
+
+/// Settings given on the command line, they override all other layers.
+#[derive(Debug, Default)]
+pub struct Overrides {
+    pub index_dir: Option<PathBuf>,
+    pub indexes: Vec<(String, PathBuf)>,
+    pub bind: Option<String>,
+}
+
+impl Config {
+    /// Load the config, `file` falls back to the `BOOK_SEARCHER_CONFIG` environment variable.
+    pub fn load(file: Option<&Path>, overrides: Overrides) -> anyhow::Result<Self> {
+        let file = file
+            .map(Path::to_path_buf)
+            .or_else(|| env::var_os(format!("{ENV_PREFIX}CONFIG")).map(PathBuf::from));
+
+        let mut config = match file {
+            Some(file) => {
+                let content = fs::read_to_string(&file)
+                    .with_context(|| format!("failed to read config {}", file.display()))?;
+                toml::from_str(&content)
+                    .with_context(|| format!("invalid config {}", file.display()))?
+            }
+            None => Config::default(),
+        };
+
+        config.merge_env()?;
+
+        if let Some(index_dir) = overrides.index_dir {
+            config.index_dir = index_dir;
+        }
+        if !overrides.indexes.is_empty() {
+            config.indexes = overrides.indexes.into_iter().collect();
+        }
+        if let Some(bind) = overrides.bind {
+            config.bind = bind;
+        }
+        Ok(config)
+    }
+
+    /// Analyzer options of latin text from `stem_language` and `ascii_folding`.
+    pub fn latin_options(&self) -> anyhow::Result<LatinOptions> {
+        let stemmer = match self.stem_language.trim() {
+            "" | "none" => None,
+            name => Some(
+                stemmer_language(name)
+                    .with_context(|| format!("unsupported stem_language {name:?}"))?,
+            ),
+        };
+        Ok(LatinOptions {
+            ascii_folding: self.ascii_folding,
+            stemmer,
+        })
+    }
+
+    /// Index directories to serve by name, `index_dir` is named `default`.
+    pub fn indexes(&self) -> BTreeMap<String, PathBuf> {
+        if self.indexes.is_empty() {
+            BTreeMap::from([("default".to_owned(), self.index_dir.clone())])
+        } else {
+            self.indexes.clone()
+        }
+    }
+
+    fn merge_env(&mut self) -> anyhow::Result<()> {
+        if let Some(bind) = env_var("BIND") {
+            self.bind = bind;
+        }
+        if let Some(index_dir) = env_var("INDEX_DIR") {
+            self.index_dir = PathBuf::from(index_dir);
+        }
+        if let Some(indexes) = env_var("INDEXES") {
+            self.indexes = split_list(&indexes)
+                .map(parse_named_index)
+                .collect::<anyhow::Result<_>>()?;
+        }
+        if let Some(default_limit) = env_parse("DEFAULT_LIMIT")? {
+            self.default_limit = default_limit;
+        }
+        if let Some(max_limit) = env_parse("MAX_LIMIT")? {
+            self.max_limit = max_limit;
+        }
+        if let Some(max_offset) = env_parse("MAX_OFFSET")? {
+            self.max_offset = max_offset;
+        }
+        if let Some(cors_origins) = env_var("CORS_ORIGINS") {
+            self.cors_origins = split_list(&cors_origins).map(ToOwned::to_owned).collect();
+        }
+        if let Some(ipfs_gateway) = env_var("IPFS_GATEWAY") {
+            self.ipfs_gateway = ipfs_gateway;
+        }
+        if let Some(keep_traditional) = env_parse("KEEP_TRADITIONAL")? {
+            self.keep_traditional = keep_traditional;
+        }
+        if let Some(stem_language) = env_var("STEM_LANGUAGE") {
+            self.stem_language = stem_language;
+        }
+        if let Some(ascii_folding) = env_parse("ASCII_FOLDING")? {
+            self.ascii_folding = ascii_folding;
+        }
+        if let Some(admin_token) = env_var("ADMIN_TOKEN") {
+            self.admin_token = Some(admin_token).filter(|token| !token.is_empty());
+        }
+        Ok(())
+    }
+}
+
+/// Parse a `NAME=DIR` index mount.
+pub fn parse_named_index(s: &str) -> anyhow::Result<(String, PathBuf)> {
+    match s.split_once('=') {
+        Some((name, dir)) if !name.trim().is_empty() && !dir.trim().is_empty() => {
+            Ok((name.trim().to_owned(), PathBuf::from(dir.trim())))
+        }
+        _ => anyhow::bail!("invalid index {s:?}, expected NAME=DIR"),
+    }
+}
+
+fn split_list(s: &str) -> impl Iterator<Item = &str> {
+    s.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(format!("{ENV_PREFIX}{name}")).ok()
+}
+
+fn env_parse<T>(name: &str) -> anyhow::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    env_var(name)
+        .map(|value| {
+            value
+                .parse()
+                .with_context(|| format!("invalid {ENV_PREFIX}{name}: {value}"))
+        })
+        .transpose()
+}
+
+#[test]
+fn test_parse_named_index() {
+    assert_eq!(
+        parse_named_index(" zh = /data/zh ").unwrap(),
+        ("zh".to_owned(), PathBuf::from("/data/zh"))
+    );
+    // only the first `=` separates the name
+    assert_eq!(
+        parse_named_index("en=/data/a=b").unwrap(),
+        ("en".to_owned(), PathBuf::from("/data/a=b"))
+    );
+    assert!(parse_named_index("/data/zh").is_err());
+    assert!(parse_named_index("=/data/zh").is_err());
+    assert!(parse_named_index("zh=").is_err());
+}
+
+#[test]
+fn test_indexes() {
+    let mut config = Config {
+        index_dir: PathBuf::from("/data/index"),
+        ..Default::default()
+    };
+    assert_eq!(
+        config.indexes(),
+        BTreeMap::from([("default".to_owned(), PathBuf::from("/data/index"))])
+    );
+
+    config.indexes = BTreeMap::from([("zh".to_owned(), PathBuf::from("/data/zh"))]);
+    assert_eq!(config.indexes(), config.indexes);
+}
+
+#[test]
+fn test_load_layers() {
+    let file = env::temp_dir().join(format!("book-searcher-config-{}.toml", std::process::id()));
+    fs::write(
+        &file,
+        r#"
+bind = "0.0.0.0:8080"
+default_limit = 20
+max_limit = 50
+cors_origins = ["https://a.example"]
+"#,
+    )
+    .unwrap();
+    let load = |overrides| Config::load(Some(&file), overrides).unwrap();
+    let vars = ["BIND", "MAX_LIMIT", "CORS_ORIGINS"].map(|name| format!("{ENV_PREFIX}{name}"));
+    for var in &vars {
+        env::remove_var(var);
+    }
+
+    // the file overrides the defaults it sets
+    let config = load(Overrides::default());
+    assert_eq!(config.bind, "0.0.0.0:8080");
+    assert_eq!((config.default_limit, config.max_limit), (20, 50));
+    assert_eq!(config.cors_origins, ["https://a.example"]);
+    assert_eq!(config.max_offset, Config::default().max_offset);
+
+    // the environment overrides the file
+    env::set_var(&vars[0], "0.0.0.0:9090");
+    env::set_var(&vars[1], "200");
+    env::set_var(&vars[2], "https://b.example, https://c.example");
+    let config = load(Overrides::default());
+    assert_eq!(config.bind, "0.0.0.0:9090");
+    assert_eq!((config.default_limit, config.max_limit), (20, 200));
+    assert_eq!(
+        config.cors_origins,
+        ["https://b.example", "https://c.example"]
+    );
+
+    // the command line overrides the environment
+    let config = load(Overrides {
+        bind: Some("127.0.0.1:7171".to_owned()),
+        ..Default::default()
+    });
+    assert_eq!(config.bind, "127.0.0.1:7171");
+    assert_eq!(config.max_limit, 200);
+
+    env::set_var(&vars[1], "many");
+    assert!(Config::load(Some(&file), Overrides::default()).is_err());
+
+    for var in &vars {
+        env::remove_var(var);
+    }
+    fs::remove_file(&file).unwrap();
+}
@@ -0,0 +1,94 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+
+/// Error returned by the http api, rendered as `{"error": CODE, "message": MESSAGE}`.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    InvalidQuery(String),
+    #[error("{0}")]
+    InvalidParameter(String),
+    #[error("index not found: {0}")]
+    IndexNotFound(String),
+    #[error("book not found")]
+    BookNotFound,
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    NotReady(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ErrorBody {
+    /// one of `invalid_query`, `invalid_parameter`, `index_not_found`, `book_not_found`, `unauthorized`, `not_ready`, `internal_error`
+    error: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidQuery(_) => "invalid_query",
+            Self::InvalidParameter(_) => "invalid_parameter",
+            Self::IndexNotFound(_) => "index_not_found",
+            Self::BookNotFound => "book_not_found",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::NotReady(_) => "not_ready",
+            Self::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidQuery(_) | Self::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+            Self::IndexNotFound(_) | Self::BookNotFound => StatusCode::NOT_FOUND,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::NotReady(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            error: self.code(),
+            message: self.to_string(),
+        })
+    }
+}
+
+impl From<book_searcher_core::Error> for ApiError {
+    fn from(err: book_searcher_core::Error) -> Self {
+        match err {
+            book_searcher_core::Error::Query(err) => Self::InvalidQuery(err.to_string()),
+            err => Self::Internal(err.to_string()),
+        }
+    }
+}
+
+#[test]
+fn test_error_response() {
+    use tantivy::query::QueryParserError;
+
+    let err: ApiError =
+        book_searcher_core::Error::Query(QueryParserError::SyntaxError("title:(".to_owned()))
+            .into();
+    assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    assert_eq!(err.code(), "invalid_query");
+
+    let err: ApiError = book_searcher_core::Error::InvalidData("broken".to_owned()).into();
+    assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(err.code(), "internal_error");
+
+    assert_eq!(
+        ApiError::IndexNotFound("zh".to_owned()).status_code(),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        ApiError::NotReady(String::new()).status_code(),
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+}
@@ -1,72 +1,550 @@
+use actix_cors::Cors;
 use actix_web::{
-    get, http::header, middleware::Logger, web, App, HttpResponse, HttpServer, Responder,
+    dev::Service, get, http::header, middleware::Logger, post, web, App, HttpRequest, HttpResponse,
+    HttpServer, Responder,
 };
 use actix_web_static_files::ResourceFiles;
-use book_searcher_core::{Book, Searcher};
+use arc_swap::ArcSwap;
+use book_searcher_core::{
+    search::{SearchQuery, SearchResult, SuggestField},
+    Book, FieldMapping, IndexMode, InputFormat, InputSource, LatinOptions, Searcher,
+};
 use clap::Parser;
-use log::info;
+use config::{Config, Overrides};
+use error::ApiError;
+use log::{error, info};
+use metrics::Metrics;
 use serde::{Deserialize, Serialize};
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+    time::Instant,
+};
+
+mod config;
+mod error;
+mod metrics;
+mod opds;
+mod openapi;
 
 include!(concat!(env!("OUT_DIR"), "/generated.rs"));
 
+struct MountedIndex {
+    dir: PathBuf,
+    searcher: ArcSwap<Searcher>,
+}
+
 #[derive(Clone)]
 struct AppState {
-    searcher: Arc<Searcher>,
+    config: Arc<Config>,
+    indexes: Arc<BTreeMap<String, MountedIndex>>,
+    metrics: Arc<Metrics>,
+    latin_options: LatinOptions,
 }
 
 impl AppState {
-    pub fn init(index_dir: &str) -> Self {
+    pub fn init(config: Config) -> anyhow::Result<Self> {
         info!("AppState init!");
-        AppState {
-            searcher: Arc::new(Searcher::new(index_dir)),
+        let metrics = Arc::new(Metrics::new()?);
+        let latin_options = config.latin_options()?;
+        let mut indexes = BTreeMap::new();
+        for (name, dir) in config.indexes() {
+            info!("Opening index {name} from {}", dir.display());
+            let searcher = Searcher::new(&dir)?;
+            let searcher =
+                ArcSwap::from_pointee(mount_searcher(&metrics, &name, searcher, latin_options)?);
+            indexes.insert(name, MountedIndex { dir, searcher });
+        }
+        Ok(AppState {
+            config: Arc::new(config),
+            indexes: Arc::new(indexes),
+            metrics,
+            latin_options,
+        })
+    }
+
+    /// Reopen the indexes and swap in the new searchers,
+    /// requests already running keep using the old ones.
+    ///
+    /// Nothing is swapped when any index is missing or fails to open.
+    pub fn reload(&self) -> book_searcher_core::Result<()> {
+        let mut searchers = Vec::with_capacity(self.indexes.len());
+        for (name, index) in self.indexes.iter() {
+            let searcher = Searcher::open(&index.dir)?;
+            searchers.push(mount_searcher(
+                &self.metrics,
+                name,
+                searcher,
+                self.latin_options,
+            )?);
+        }
+        for ((name, index), searcher) in self.indexes.iter().zip(searchers) {
+            index.searcher.store(Arc::new(searcher));
+            info!("Index {name} reloaded from {}", index.dir.display());
+        }
+        Ok(())
+    }
+
+    /// Searchers of the named index, or of all indexes for `None` and `*`.
+    fn searchers(&self, name: Option<&str>) -> Result<Vec<(&str, Arc<Searcher>)>, ApiError> {
+        match name {
+            None | Some("*") => Ok(self
+                .indexes
+                .iter()
+                .map(|(name, index)| (name.as_str(), index.searcher.load_full()))
+                .collect()),
+            Some(name) => self
+                .indexes
+                .get_key_value(name)
+                .map(|(name, index)| vec![(name.as_str(), index.searcher.load_full())])
+                .ok_or_else(|| ApiError::IndexNotFound(name.to_owned())),
         }
     }
 }
 
-fn default_limit() -> usize {
-    30
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct IndexQuery {
+    /// name of the index, all indexes when missing or `*`
+    index: Option<String>,
 }
 
-#[derive(Deserialize)]
-struct SearchQuery {
-    #[serde(flatten)]
-    query: book_searcher_core::search::SearchQuery,
-    #[serde(default = "default_limit")]
-    limit: usize,
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct Page {
+    /// number of books to return, defaults to `default_limit`
+    limit: Option<usize>,
+    /// number of books to skip
     #[serde(default)]
     offset: usize,
 }
 
-#[derive(Serialize)]
-struct SearchResult {
-    total: usize,
+#[derive(Serialize, utoipa::ToSchema)]
+struct SearchResponse {
     offset: usize,
     limit: usize,
-    books: Vec<Book>,
+    #[serde(flatten)]
+    result: SearchResult,
+}
+
+/// Configure a newly opened searcher of the named index.
+fn mount_searcher(
+    metrics: &Arc<Metrics>,
+    name: &str,
+    mut searcher: Searcher,
+    latin_options: LatinOptions,
+) -> book_searcher_core::Result<Searcher> {
+    searcher.set_latin_options(latin_options);
+    searcher.set_hook(metrics.hook(name))?;
+    Ok(searcher)
 }
 
+fn check_limit(limit: usize, max_limit: usize) -> Result<(), ApiError> {
+    if limit > max_limit {
+        return Err(ApiError::InvalidParameter(format!(
+            "limit must not be greater than {max_limit}"
+        )));
+    }
+    Ok(())
+}
+
+/// Search the named index, or all indexes merged when no name is given.
+fn search_books(
+    state: &AppState,
+    name: Option<&str>,
+    query: &SearchQuery,
+    page: &Page,
+) -> Result<SearchResponse, ApiError> {
+    let searchers = state.searchers(name)?;
+    let limit = page.limit.unwrap_or(state.config.default_limit);
+    check_limit(limit, state.config.max_limit)?;
+    if page.offset > state.config.max_offset {
+        return Err(ApiError::InvalidParameter(format!(
+            "offset must not be greater than {}",
+            state.config.max_offset
+        )));
+    }
+
+    let result = if let [(_, searcher)] = searchers.as_slice() {
+        searcher.search(query, limit, page.offset)?
+    } else {
+        let results = searchers
+            .iter()
+            .map(|(name, searcher)| {
+                let result = searcher.search(query, page.offset + limit, 0)?;
+                Ok((name.to_string(), result))
+            })
+            .collect::<book_searcher_core::Result<_>>()?;
+        SearchResult::merge(results, query, limit, page.offset)
+    };
+
+    Ok(SearchResponse {
+        offset: page.offset,
+        limit,
+        result,
+    })
+}
+
+#[utoipa::path(
+    tag = "search",
+    params(SearchQuery, IndexQuery, Page),
+    responses(
+        (status = 200, description = "matched books", body = SearchResponse),
+        (status = 400, description = "invalid query or parameter", body = ErrorBody),
+        (status = 404, description = "index not found", body = ErrorBody),
+    )
+)]
 #[get("/search")]
-async fn search(query: web::Query<SearchQuery>, state: web::Data<AppState>) -> impl Responder {
-    let (books, count) = state
-        .searcher
-        .search(&query.query, query.limit, query.offset);
-    let result = SearchResult {
-        total: count,
-        offset: query.offset,
-        limit: query.limit,
-        books,
+async fn search(
+    query: web::Query<SearchQuery>,
+    index: web::Query<IndexQuery>,
+    page: web::Query<Page>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let result = search_books(&state, index.index.as_deref(), &query, &page)?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[utoipa::path(
+    tag = "search",
+    params(("name", description = "name of the index"), SearchQuery, Page),
+    responses(
+        (status = 200, description = "matched books", body = SearchResponse),
+        (status = 400, description = "invalid query or parameter", body = ErrorBody),
+        (status = 404, description = "index not found", body = ErrorBody),
+    )
+)]
+#[get("/indexes/{name}/search")]
+async fn search_index(
+    name: web::Path<String>,
+    query: web::Query<SearchQuery>,
+    page: web::Query<Page>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let result = search_books(&state, Some(&name), &query, &page)?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[utoipa::path(
+    tag = "search",
+    responses((status = 200, description = "names of the served indexes", body = [String]))
+)]
+#[get("/indexes")]
+async fn list_indexes(state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(state.indexes.keys().collect::<Vec<_>>())
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct Status {
+    status: &'static str,
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, description = "the webserver is running", body = Status))
+)]
+#[get("/healthz")]
+async fn healthz() -> impl Responder {
+    HttpResponse::Ok().json(Status { status: "ok" })
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses(
+        (status = 200, description = "all indexes are open and contain books", body = Status),
+        (status = 503, description = "an index is empty", body = ErrorBody)
+    )
+)]
+#[get("/readyz")]
+async fn readyz(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    for (name, index) in state.indexes.iter() {
+        if index.searcher.load().num_docs() == 0 {
+            return Err(ApiError::NotReady(format!("index {name} is empty")));
+        }
+    }
+    Ok(HttpResponse::Ok().json(Status { status: "ready" }))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    params(IndexQuery),
+    responses(
+        (status = 200, description = "statistics keyed by index name", body = HashMap<String, IndexStats>),
+        (status = 404, description = "index not found", body = ErrorBody)
+    )
+)]
+#[get("/stats")]
+async fn stats(
+    query: web::Query<IndexQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let mut stats = BTreeMap::new();
+    for (name, searcher) in state.searchers(query.index.as_deref())? {
+        stats.insert(name, searcher.stats()?);
+    }
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+fn default_suggest_limit() -> usize {
+    10
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SuggestQuery {
+    /// beginning of a title or author
+    prefix: String,
+    /// field to complete
+    #[serde(default)]
+    field: SuggestField,
+    /// number of suggestions, defaults to 10
+    #[serde(default = "default_suggest_limit")]
+    limit: usize,
+}
+
+/// Suggest from the named index, or from all indexes when no name is given.
+fn suggest_indexes(
+    state: &AppState,
+    name: Option<&str>,
+    query: &SuggestQuery,
+) -> Result<HttpResponse, ApiError> {
+    let searchers = state.searchers(name)?;
+    check_limit(query.limit, state.config.max_limit)?;
+
+    let mut seen = HashSet::new();
+    let mut suggestions = Vec::new();
+    for (_, searcher) in searchers {
+        let items = searcher.suggest(&query.prefix, query.field, query.limit)?;
+        suggestions.extend(
+            items
+                .into_iter()
+                .filter(|item| seen.insert(item.to_lowercase())),
+        );
+    }
+    suggestions.truncate(query.limit);
+
+    Ok(HttpResponse::Ok().json(suggestions))
+}
+
+#[utoipa::path(
+    tag = "search",
+    params(SuggestQuery, IndexQuery),
+    responses(
+        (status = 200, description = "distinct completions", body = [String]),
+        (status = 400, description = "invalid query or parameter", body = ErrorBody),
+        (status = 404, description = "index not found", body = ErrorBody),
+    )
+)]
+#[get("/suggest")]
+async fn suggest(
+    query: web::Query<SuggestQuery>,
+    index: web::Query<IndexQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    suggest_indexes(&state, index.index.as_deref(), &query)
+}
+
+#[utoipa::path(
+    tag = "search",
+    params(("name", description = "name of the index"), SuggestQuery),
+    responses(
+        (status = 200, description = "distinct completions", body = [String]),
+        (status = 400, description = "invalid query or parameter", body = ErrorBody),
+        (status = 404, description = "index not found", body = ErrorBody),
+    )
+)]
+#[get("/indexes/{name}/suggest")]
+async fn suggest_index(
+    name: web::Path<String>,
+    query: web::Query<SuggestQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    suggest_indexes(&state, Some(&name), &query)
+}
+
+/// Look up a book in the named index, or in each index in turn when no name is given.
+fn lookup_book(
+    state: &AppState,
+    name: Option<&str>,
+    lookup: impl Fn(&Searcher) -> book_searcher_core::Result<Option<Book>>,
+) -> Result<HttpResponse, ApiError> {
+    for (_, searcher) in state.searchers(name)? {
+        if let Some(book) = lookup(&searcher)? {
+            return Ok(HttpResponse::Ok().json(book));
+        }
+    }
+    Err(ApiError::BookNotFound)
+}
+
+#[utoipa::path(
+    tag = "books",
+    params(
+        ("md5", description = "md5 of the book file"),
+        IndexQuery,
+    ),
+    responses(
+        (status = 200, description = "the book", body = Book),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+        (status = 404, description = "book or index not found", body = ErrorBody),
+    )
+)]
+#[get("/books/md5/{md5}")]
+async fn book_by_md5(
+    md5: web::Path<String>,
+    query: web::Query<IndexQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    lookup_book(&state, query.index.as_deref(), |searcher| {
+        searcher.get_by_md5(&md5)
+    })
+}
+
+#[utoipa::path(
+    tag = "books",
+    params(
+        ("cid", description = "IPFS CID of the book file"),
+        IndexQuery,
+    ),
+    responses(
+        (status = 200, description = "the book", body = Book),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+        (status = 404, description = "book or index not found", body = ErrorBody),
+    )
+)]
+#[get("/books/cid/{cid}")]
+async fn book_by_cid(
+    cid: web::Path<String>,
+    query: web::Query<IndexQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    lookup_book(&state, query.index.as_deref(), |searcher| {
+        searcher.get_by_cid(&cid)
+    })
+}
+
+#[utoipa::path(
+    tag = "books",
+    params(
+        ("isbn", description = "ISBN of the books"),
+        IndexQuery,
+    ),
+    responses(
+        (status = 200, description = "books with the ISBN", body = [Book]),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+        (status = 404, description = "index not found", body = ErrorBody),
+    )
+)]
+#[get("/books/isbn/{isbn}")]
+async fn books_by_isbn(
+    isbn: web::Path<String>,
+    query: web::Query<IndexQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let mut books = Vec::new();
+    for (_, searcher) in state.searchers(query.index.as_deref())? {
+        books.extend(searcher.get_by_isbn(&isbn)?);
+    }
+    Ok(HttpResponse::Ok().json(books))
+}
+
+#[utoipa::path(
+    tag = "books",
+    params(
+        ("id" = u64, description = "id of the book"),
+        IndexQuery,
+    ),
+    responses(
+        (status = 200, description = "the book", body = Book),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+        (status = 404, description = "book or index not found", body = ErrorBody),
+    )
+)]
+#[get("/books/{id}")]
+async fn book_by_id(
+    id: web::Path<u64>,
+    query: web::Query<IndexQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    lookup_book(&state, query.index.as_deref(), |searcher| {
+        searcher.get_by_id(*id)
+    })
+}
+
+/// Check the `Authorization: Bearer TOKEN` header against `admin_token`.
+fn check_admin(req: &HttpRequest, config: &Config) -> Result<(), ApiError> {
+    let Some(ref admin_token) = config.admin_token else {
+        return Err(ApiError::Unauthorized(
+            "admin api is disabled, set admin_token to enable it".to_owned(),
+        ));
     };
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !token.map_or(false, |token| {
+        constant_time_eq(token.trim().as_bytes(), admin_token.as_bytes())
+    }) {
+        return Err(ApiError::Unauthorized(
+            "missing or invalid admin token".to_owned(),
+        ));
+    }
+    Ok(())
+}
 
-    return HttpResponse::Ok()
-        .insert_header(header::ContentType::json())
-        .insert_header((header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"))
-        .json(result);
+/// Compare without returning early on the first different byte,
+/// so the time taken does not tell how much of a guessed token is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[utoipa::path(
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "indexes reloaded"),
+        (status = 401, description = "missing or invalid admin token", body = ErrorBody),
+        (status = 500, description = "failed to reload", body = ErrorBody),
+    )
+)]
+#[post("/admin/reload")]
+async fn reload(req: HttpRequest, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    check_admin(&req, &state.config)?;
+    web::block(move || state.reload())
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))??;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[cfg(unix)]
+fn reload_on_sighup(state: AppState) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    actix_web::rt::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading index");
+            let state = state.clone();
+            match web::block(move || state.reload()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => error!("Failed to reload index: {err}"),
+                Err(err) => error!("Failed to reload index: {err}"),
+            }
+        }
+    });
+    Ok(())
 }
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about)]
 struct AppOpts {
+    #[clap(long, global = true, help = "specify a toml config file")]
+    config: Option<PathBuf>,
+
+    #[clap(long, global = true, help = "specify the index directory")]
+    index_dir: Option<PathBuf>,
+
     #[clap(subcommand)]
     subcmd: SubCommand,
 }
@@ -77,6 +555,10 @@ enum SubCommand {
     Run(Run),
     /// index the raw data
     Index(Index),
+    /// delete books from the index
+    Delete(Delete),
+    /// print statistics of the index
+    Stats,
 }
 
 #[derive(Parser)]
@@ -84,17 +566,36 @@ struct Run {
     #[clap(
         short,
         long,
-        default_value = "127.0.0.1:7070",
-        help = "webserver bind address"
+        help = "webserver bind address, defaults to 127.0.0.1:7070"
     )]
-    bind: String,
+    bind: Option<String>,
+
+    #[clap(
+        long = "index",
+        value_parser = config::parse_named_index,
+        help = "serve an index under a name as NAME=DIR, can be repeated"
+    )]
+    indexes: Vec<(String, PathBuf)>,
 }
 
 #[derive(Parser)]
 struct Index {
-    #[clap(short, long, num_args=1.., help = "specify csv file to be indexed")]
+    #[clap(short, long, num_args=1.., help = "specify raw data files to be indexed")]
     file: Vec<PathBuf>,
 
+    #[clap(
+        long,
+        default_value = "csv",
+        help = "specify raw data format: csv (no header), csv-header, tsv, jsonl"
+    )]
+    format: InputFormat,
+
+    #[clap(
+        long,
+        help = "specify a toml or json file mapping raw data columns to book fields"
+    )]
+    mapping: Option<PathBuf>,
+
     #[clap(
         short,
         long,
@@ -102,60 +603,222 @@ struct Index {
         help = "specify index compressor: none, lz4, zstd"
     )]
     compressor: String,
+
+    #[clap(
+        short,
+        long,
+        default_value = "append",
+        help = "specify index mode: append, upsert (by id), upsert-md5"
+    )]
+    mode: IndexMode,
 }
 
-fn main() {
+#[derive(Parser)]
+struct Delete {
+    #[clap(long, num_args=1.., help = "specify ids of the books to be deleted")]
+    id: Vec<u64>,
+}
+
+fn main() -> anyhow::Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
     let args = AppOpts::parse();
+    let mut overrides = Overrides {
+        index_dir: args.index_dir,
+        ..Default::default()
+    };
+    if let SubCommand::Run(ref opts) = args.subcmd {
+        overrides.bind = opts.bind.clone();
+        overrides.indexes = opts.indexes.clone();
+    }
+    let config = Config::load(args.config.as_deref(), overrides)?;
+
     match args.subcmd {
-        SubCommand::Run(opts) => run(opts).unwrap(),
-        SubCommand::Index(opts) => index(opts),
+        SubCommand::Run(_) => run(config)?,
+        SubCommand::Index(opts) => index(config, opts)?,
+        SubCommand::Delete(opts) => delete(config, opts)?,
+        SubCommand::Stats => stats_index(config)?,
     }
+    Ok(())
 }
 
 #[actix_web::main]
-async fn run(opts: Run) -> std::io::Result<()> {
-    info!("Webserver started: http://{}", opts.bind);
-
-    let index_dir = std::env::current_exe()
-        .unwrap()
-        .parent()
-        .unwrap()
-        .join("index")
-        .to_str()
-        .unwrap()
-        .to_string();
-    let app_state = AppState::init(&index_dir);
+async fn run(config: Config) -> std::io::Result<()> {
+    info!("Webserver started: http://{}", config.bind);
+
+    let bind = config.bind.clone();
+    let app_state = AppState::init(config)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+    #[cfg(unix)]
+    reload_on_sighup(app_state.clone())?;
 
     HttpServer::new(move || {
         let generated = generate();
+        let metrics = app_state.metrics.clone();
         App::new()
+            .wrap_fn(move |req, srv| {
+                let metrics = metrics.clone();
+                let method = req.method().to_string();
+                let start = Instant::now();
+                let response = srv.call(req);
+                async move {
+                    let response = response.await?;
+                    let route = response
+                        .request()
+                        .match_pattern()
+                        .filter(|pattern| !pattern.is_empty())
+                        .unwrap_or_else(|| "other".to_owned());
+                    let status = response.status().as_u16();
+                    metrics.observe_request(&method, &route, status, start.elapsed());
+                    Ok(response)
+                }
+            })
+            .wrap(cors(&app_state.config.cors_origins))
             .wrap(Logger::default())
             .app_data(web::Data::new(app_state.clone()))
+            .app_data(
+                web::QueryConfig::default()
+                    .error_handler(|err, _| ApiError::InvalidParameter(err.to_string()).into()),
+            )
+            .app_data(
+                web::PathConfig::default()
+                    .error_handler(|err, _| ApiError::InvalidParameter(err.to_string()).into()),
+            )
             .service(search)
+            .service(search_index)
+            .service(list_indexes)
+            .service(suggest)
+            .service(suggest_index)
+            .service(book_by_md5)
+            .service(book_by_cid)
+            .service(books_by_isbn)
+            .service(book_by_id)
+            .service(reload)
+            .service(metrics::metrics)
+            .service(healthz)
+            .service(readyz)
+            .service(stats)
+            .configure(opds::services)
+            .configure(openapi::services)
             .service(ResourceFiles::new("/", generated))
     })
-    .bind(opts.bind)?
+    .bind(bind)?
     .run()
     .await
 }
 
-fn index(opts: Index) {
-    let index_dir = std::env::current_exe()
-        .unwrap()
-        .parent()
-        .unwrap()
-        .join("index")
-        .to_str()
-        .unwrap()
-        .to_string();
-    let mut searcher = Searcher::new(index_dir);
-    searcher.set_compressor(&opts.compressor);
+fn cors(origins: &[String]) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(["GET", "POST"])
+        .allow_any_header();
+    if origins.iter().any(|origin| origin == "*") {
+        cors = cors.allow_any_origin().send_wildcard();
+    } else {
+        for origin in origins {
+            cors = cors.allowed_origin(origin);
+        }
+    }
+    cors
+}
+
+fn index(config: Config, opts: Index) -> anyhow::Result<()> {
+    let mut searcher = Searcher::new(&config.index_dir)?;
+    searcher.set_compressor(&opts.compressor)?;
+    searcher.set_keep_traditional(config.keep_traditional);
+    searcher.set_latin_options(config.latin_options()?);
+    searcher.index_mode = opts.mode;
+
+    let mapping = match opts.mapping {
+        Some(ref mapping) => Some(Arc::new(FieldMapping::from_file(mapping)?)),
+        None => None,
+    };
 
     if opts.file.is_empty() {
         println!("csv file is missing!");
     } else {
-        opts.file.iter().for_each(|file| searcher.index(file));
+        for file in &opts.file {
+            let mut source = InputSource::new(file, opts.format);
+            if let Some(ref mapping) = mapping {
+                source = source.with_mapping(mapping.clone());
+            }
+            searcher.index(&source)?;
+        }
+    }
+    Ok(())
+}
+
+fn delete(config: Config, opts: Delete) -> anyhow::Result<()> {
+    let searcher = Searcher::new(config.index_dir)?;
+
+    if opts.id.is_empty() {
+        println!("book id is missing!");
+    } else {
+        searcher.delete(&opts.id)?;
     }
+    Ok(())
+}
+
+fn stats_index(config: Config) -> anyhow::Result<()> {
+    let searcher = Searcher::new(config.index_dir)?;
+    println!("{}", serde_json::to_string_pretty(&searcher.stats()?)?);
+    Ok(())
+}
+
+#[test]
+fn test_check_admin() {
+    use actix_web::test::TestRequest;
+
+    let request = |token: Option<&str>| {
+        let mut req = TestRequest::default();
+        if let Some(token) = token {
+            req = req.insert_header((header::AUTHORIZATION, token));
+        }
+        req.to_http_request()
+    };
+
+    let mut config = Config::default();
+    assert!(check_admin(&request(Some("Bearer secret")), &config).is_err());
+
+    config.admin_token = Some("secret".to_owned());
+    assert!(check_admin(&request(Some("Bearer secret")), &config).is_ok());
+    assert!(check_admin(&request(Some("Bearer other")), &config).is_err());
+    assert!(check_admin(&request(Some("secret")), &config).is_err());
+    assert!(check_admin(&request(None), &config).is_err());
+    assert!(check_admin(&request(Some("Bearer secre")), &config).is_err());
+    assert!(check_admin(&request(Some("Bearer secrets")), &config).is_err());
+}
+
+#[test]
+fn test_reload_missing_index() {
+    let dir = std::env::temp_dir().join(format!("book-searcher-reload-{}", std::process::id()));
+    _ = std::fs::remove_dir_all(&dir);
+    let config = Config {
+        index_dir: dir.clone(),
+        ..Default::default()
+    };
+    let state = AppState::init(config).unwrap();
+    let searcher = state.searchers(None).unwrap()[0].1.clone();
+
+    state.reload().unwrap();
+    assert!(!Arc::ptr_eq(
+        &searcher,
+        &state.searchers(None).unwrap()[0].1
+    ));
+
+    // a moved index keeps the old searcher instead of creating an empty one
+    std::fs::remove_dir_all(&dir).unwrap();
+    let searcher = state.searchers(None).unwrap()[0].1.clone();
+    assert!(state.reload().is_err());
+    assert!(Arc::ptr_eq(&searcher, &state.searchers(None).unwrap()[0].1));
+    assert!(!dir.exists());
+}
+
+#[test]
+fn test_check_limit() {
+    assert!(check_limit(0, 100).is_ok());
+    assert!(check_limit(100, 100).is_ok());
+    assert!(matches!(
+        check_limit(101, 100),
+        Err(ApiError::InvalidParameter(_))
+    ));
 }
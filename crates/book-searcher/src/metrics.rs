@@ -0,0 +1,151 @@
+//! Prometheus metrics of the webserver, exported at `/metrics`.
+
+use crate::{error::ApiError, AppState};
+use actix_web::{get, web, HttpResponse};
+use book_searcher_core::SearcherHook;
+use log::error;
+use prometheus::{
+    exponential_buckets, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use std::{sync::Arc, time::Duration};
+
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    search_results: HistogramVec,
+    reloads: IntCounterVec,
+    index_docs: IntGaugeVec,
+    index_segments: IntGaugeVec,
+    index_size: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("book_searcher".to_owned()), None)?;
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of http requests"),
+            &["method", "route", "status"],
+        )?;
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Latency of http requests in seconds",
+            ),
+            &["method", "route"],
+        )?;
+        let search_results = HistogramVec::new(
+            HistogramOpts::new("search_results", "Number of books matched by a search")
+                .buckets(exponential_buckets(1.0, 10.0, 7)?),
+            &["index"],
+        )?;
+        let reloads = IntCounterVec::new(
+            Opts::new(
+                "index_reloads_total",
+                "Number of new index commits picked up",
+            ),
+            &["index"],
+        )?;
+        let index_docs = IntGaugeVec::new(
+            Opts::new("index_documents", "Number of books in the index"),
+            &["index"],
+        )?;
+        let index_segments = IntGaugeVec::new(
+            Opts::new("index_segments", "Number of segments of the index"),
+            &["index"],
+        )?;
+        let index_size = IntGaugeVec::new(
+            Opts::new("index_size_bytes", "Size of the index directory on disk"),
+            &["index"],
+        )?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+        registry.register(Box::new(search_results.clone()))?;
+        registry.register(Box::new(reloads.clone()))?;
+        registry.register(Box::new(index_docs.clone()))?;
+        registry.register(Box::new(index_segments.clone()))?;
+        registry.register(Box::new(index_size.clone()))?;
+
+        Ok(Self {
+            registry,
+            requests,
+            request_duration,
+            search_results,
+            reloads,
+            index_docs,
+            index_segments,
+            index_size,
+        })
+    }
+
+    /// Hook recording the searches and reloads of the named index.
+    pub fn hook(self: &Arc<Self>, index: &str) -> Arc<dyn SearcherHook> {
+        Arc::new(IndexHook {
+            metrics: self.clone(),
+            index: index.to_owned(),
+        })
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.request_duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Update the index gauges and encode all metrics in the text format.
+    fn render(&self, state: &AppState) -> prometheus::Result<String> {
+        for (name, index) in state.indexes.iter() {
+            let searcher = index.searcher.load();
+            self.index_docs
+                .with_label_values(&[name])
+                .set(searcher.num_docs() as i64);
+            self.index_segments
+                .with_label_values(&[name])
+                .set(searcher.num_segments() as i64);
+            match searcher.index_size() {
+                Ok(size) => self.index_size.with_label_values(&[name]).set(size as i64),
+                Err(err) => error!("Failed to get size of index {name}: {err}"),
+            }
+        }
+        TextEncoder::new().encode_to_string(&self.registry.gather())
+    }
+}
+
+struct IndexHook {
+    metrics: Arc<Metrics>,
+    index: String,
+}
+
+impl SearcherHook for IndexHook {
+    fn searched(&self, total: usize) {
+        self.metrics
+            .search_results
+            .with_label_values(&[&self.index])
+            .observe(total as f64);
+    }
+
+    fn reloaded(&self) {
+        self.metrics.reloads.with_label_values(&[&self.index]).inc();
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, description = "metrics in the prometheus text format", body = String))
+)]
+#[get("/metrics")]
+pub(crate) async fn metrics(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let body = state
+        .metrics
+        .render(&state)
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(body))
+}
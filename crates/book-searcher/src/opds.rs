@@ -0,0 +1,367 @@
+//! OPDS 1.2 catalog, so that e-reader apps like KOReader can search and download books.
+
+use crate::{error::ApiError, search_books, AppState, IndexQuery, Page};
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use book_searcher_core::search::{escape_html, SearchHit, SearchMode, SearchQuery};
+use std::fmt::Write;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+const NAVIGATION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+const ACQUISITION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+const OPENSEARCH_TYPE: &str = "application/opensearchdescription+xml";
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(root).service(opensearch).service(search);
+}
+
+#[utoipa::path(
+    tag = "opds",
+    params(IndexQuery),
+    responses((status = 200, description = "OPDS navigation feed", content_type = "application/atom+xml", body = String))
+)]
+#[get("/opds")]
+async fn root(req: HttpRequest, query: web::Query<IndexQuery>) -> impl Responder {
+    let base = base_url(&req);
+    let index = index_param(query.index.as_deref());
+    let url = format!("{base}/opds{index}");
+
+    let mut feed = feed_start(&url, "Book Searcher");
+    link(&mut feed, "self", &url, NAVIGATION_TYPE);
+    link(&mut feed, "start", &url, NAVIGATION_TYPE);
+    link(
+        &mut feed,
+        "search",
+        &format!("{base}/opds/opensearch.xml{index}"),
+        OPENSEARCH_TYPE,
+    );
+    feed.push_str("</feed>\n");
+
+    atom(feed, NAVIGATION_TYPE)
+}
+
+#[utoipa::path(
+    tag = "opds",
+    params(IndexQuery),
+    responses((status = 200, description = "OpenSearch description", content_type = "application/opensearchdescription+xml", body = String))
+)]
+#[get("/opds/opensearch.xml")]
+async fn opensearch(req: HttpRequest, query: web::Query<IndexQuery>) -> impl Responder {
+    let mut template = format!("{}/opds/search?query={{searchTerms}}", base_url(&req));
+    if let Some(index) = &query.index {
+        template.push('&');
+        template.push_str(&serde_urlencoded::to_string([("index", index)]).unwrap_or_default());
+    }
+
+    let mut description = String::new();
+    _ = write!(
+        description,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">
+  <ShortName>Book Searcher</ShortName>
+  <Description>Search books by title, author and publisher</Description>
+  <InputEncoding>UTF-8</InputEncoding>
+  <OutputEncoding>UTF-8</OutputEncoding>
+  <Url type="{}" template="{}"/>
+</OpenSearchDescription>
+"#,
+        escape_html(ACQUISITION_TYPE),
+        escape_html(&template)
+    );
+
+    HttpResponse::Ok()
+        .content_type(format!("{OPENSEARCH_TYPE}; charset=utf-8"))
+        .body(description)
+}
+
+#[utoipa::path(
+    tag = "opds",
+    params(SearchQuery, IndexQuery, Page),
+    responses(
+        (status = 200, description = "OPDS acquisition feed", content_type = "application/atom+xml", body = String),
+        (status = 400, description = "invalid query or parameter", body = ErrorBody),
+        (status = 404, description = "index not found", body = ErrorBody),
+    )
+)]
+#[get("/opds/search")]
+async fn search(
+    req: HttpRequest,
+    query: web::Query<SearchQuery>,
+    index: web::Query<IndexQuery>,
+    page: web::Query<Page>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    // e-readers send free text, never fail on query syntax like `:` or `(`
+    let mut query = query.into_inner();
+    query.mode = SearchMode::Fuzzy;
+    let result = search_books(&state, index.index.as_deref(), &query, &page)?;
+    let base = base_url(&req);
+    let (offset, limit, total) = (result.offset, result.limit, result.result.total);
+    let url = page_url(&base, req.query_string(), offset, limit);
+
+    let mut feed = feed_start(&url, "Search results");
+    _ = writeln!(
+        feed,
+        "  <opensearch:totalResults>{total}</opensearch:totalResults>\n  \
+         <opensearch:itemsPerPage>{limit}</opensearch:itemsPerPage>\n  \
+         <opensearch:startIndex>{}</opensearch:startIndex>",
+        offset + 1
+    );
+    link(&mut feed, "self", &url, ACQUISITION_TYPE);
+    link(
+        &mut feed,
+        "start",
+        &format!("{base}/opds{}", index_param(index.index.as_deref())),
+        NAVIGATION_TYPE,
+    );
+    if offset > 0 {
+        let first = page_url(&base, req.query_string(), 0, limit);
+        let previous = page_url(
+            &base,
+            req.query_string(),
+            offset.saturating_sub(limit),
+            limit,
+        );
+        link(&mut feed, "first", &first, ACQUISITION_TYPE);
+        link(&mut feed, "previous", &previous, ACQUISITION_TYPE);
+    }
+    if limit > 0 && offset + limit < total {
+        let next = page_url(&base, req.query_string(), offset + limit, limit);
+        link(&mut feed, "next", &next, ACQUISITION_TYPE);
+    }
+
+    for hit in &result.result.books {
+        entry(&mut feed, hit, &state.config.ipfs_gateway);
+    }
+    feed.push_str("</feed>\n");
+
+    Ok(atom(feed, ACQUISITION_TYPE))
+}
+
+fn entry(feed: &mut String, hit: &SearchHit, ipfs_gateway: &str) {
+    let book = &hit.book;
+    feed.push_str("  <entry>\n");
+    _ = writeln!(feed, "    <title>{}</title>", escape_html(&book.title));
+    match &hit.index {
+        Some(index) => {
+            _ = writeln!(
+                feed,
+                "    <id>urn:book-searcher:{}:{}</id>",
+                escape_html(index),
+                book.id
+            )
+        }
+        None => _ = writeln!(feed, "    <id>urn:book-searcher:book:{}</id>", book.id),
+    }
+    _ = writeln!(feed, "    <updated>{}</updated>", now());
+    if !book.author.is_empty() {
+        _ = writeln!(
+            feed,
+            "    <author><name>{}</name></author>",
+            escape_html(&book.author)
+        );
+    }
+    if !book.publisher.is_empty() {
+        _ = writeln!(
+            feed,
+            "    <dc:publisher>{}</dc:publisher>",
+            escape_html(&book.publisher)
+        );
+    }
+    if book.year > 0 {
+        _ = writeln!(feed, "    <dc:issued>{}</dc:issued>", book.year);
+    }
+    if !book.language.is_empty() {
+        _ = writeln!(
+            feed,
+            "    <dc:language>{}</dc:language>",
+            escape_html(&book.language)
+        );
+    }
+    if !book.isbn.is_empty() {
+        _ = writeln!(
+            feed,
+            "    <dc:identifier>urn:isbn:{}</dc:identifier>",
+            escape_html(&book.isbn)
+        );
+    }
+    _ = writeln!(
+        feed,
+        "    <content type=\"text\">{}, {}</content>",
+        escape_html(&book.extension.to_uppercase()),
+        human_size(book.filesize)
+    );
+
+    if !book.cover_url.is_empty() {
+        link(
+            feed,
+            "http://opds-spec.org/image",
+            &book.cover_url,
+            "image/jpeg",
+        );
+        link(
+            feed,
+            "http://opds-spec.org/image/thumbnail",
+            &book.cover_url,
+            "image/jpeg",
+        );
+    }
+    if !book.ipfs_cid.is_empty() {
+        let filename = format!("{}.{}", book.title, book.extension);
+        let href = format!(
+            "{}/ipfs/{}?{}",
+            ipfs_gateway.trim_end_matches('/'),
+            book.ipfs_cid,
+            serde_urlencoded::to_string([("filename", filename)]).unwrap_or_default()
+        );
+        link(
+            feed,
+            "http://opds-spec.org/acquisition",
+            &href,
+            mime_type(&book.extension),
+        );
+    }
+    feed.push_str("  </entry>\n");
+}
+
+fn feed_start(id: &str, title: &str) -> String {
+    let mut feed = String::new();
+    _ = write!(
+        feed,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xmlns:dc="http://purl.org/dc/terms/" xmlns:opds="http://opds-spec.org/2010/catalog" xmlns:opensearch="http://a9.com/-/spec/opensearch/1.1/">
+  <id>{}</id>
+  <title>{}</title>
+  <updated>{}</updated>
+"#,
+        escape_html(id),
+        escape_html(title),
+        now()
+    );
+    feed
+}
+
+fn link(feed: &mut String, rel: &str, href: &str, mime_type: &str) {
+    _ = writeln!(
+        feed,
+        r#"  <link rel="{}" href="{}" type="{}"/>"#,
+        escape_html(rel),
+        escape_html(href),
+        escape_html(mime_type)
+    );
+}
+
+fn atom(body: String, mime_type: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(format!("{mime_type}; charset=utf-8"))
+        .body(body)
+}
+
+fn base_url(req: &HttpRequest) -> String {
+    let info = req.connection_info();
+    format!("{}://{}", info.scheme(), info.host())
+}
+
+fn index_param(index: Option<&str>) -> String {
+    match index {
+        Some(index) => format!(
+            "?{}",
+            serde_urlencoded::to_string([("index", index)]).unwrap_or_default()
+        ),
+        None => String::new(),
+    }
+}
+
+/// Url of a search page, keeping all other parameters of the current search.
+fn page_url(base: &str, query_string: &str, offset: usize, limit: usize) -> String {
+    let mut params: Vec<(String, String)> =
+        serde_urlencoded::from_str(query_string).unwrap_or_default();
+    params.retain(|(key, _)| key != "offset" && key != "limit");
+    params.push(("limit".to_owned(), limit.to_string()));
+    params.push(("offset".to_owned(), offset.to_string()));
+    format!(
+        "{base}/opds/search?{}",
+        serde_urlencoded::to_string(params).unwrap_or_default()
+    )
+}
+
+fn now() -> String {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default()
+}
+
+fn mime_type(extension: &str) -> &'static str {
+    match extension.to_lowercase().as_str() {
+        "epub" => "application/epub+zip",
+        "pdf" => "application/pdf",
+        "mobi" => "application/x-mobipocket-ebook",
+        "azw3" => "application/vnd.amazon.ebook",
+        "fb2" => "application/x-fictionbook+xml",
+        "djvu" => "image/vnd.djvu",
+        "txt" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+#[test]
+fn test_entry_escaping() {
+    use book_searcher_core::Book;
+
+    let hit = SearchHit {
+        book: Book {
+            id: 7,
+            title: "Tom & Jerry <\"2\">".to_owned(),
+            author: "O'Brien".to_owned(),
+            extension: "epub".to_owned(),
+            ipfs_cid: "bafy".to_owned(),
+            ..Default::default()
+        },
+        highlight: None,
+        index: Some("a&b".to_owned()),
+        score: 0.0,
+    };
+    let mut feed = String::new();
+    entry(&mut feed, &hit, "https://ipfs.io/");
+
+    assert!(feed.contains("<title>Tom &amp; Jerry &lt;&quot;2&quot;&gt;</title>"));
+    assert!(feed.contains("<id>urn:book-searcher:a&amp;b:7</id>"));
+    assert!(feed.contains("<name>O&#x27;Brien</name>"));
+    assert!(feed
+        .contains(r#"href="https://ipfs.io/ipfs/bafy?filename=Tom+%26+Jerry+%3C%222%22%3E.epub""#));
+    assert!(!feed.contains("<dc:publisher>"));
+}
+
+#[test]
+fn test_page_url() {
+    let base = "http://localhost:7070";
+    assert_eq!(
+        page_url(base, "query=rust&limit=10&offset=0&index=en", 10, 10),
+        "http://localhost:7070/opds/search?query=rust&index=en&limit=10&offset=10"
+    );
+    // the search terms stay url encoded, the whole url is escaped in the feed
+    let next = page_url(base, "query=tom+%26+jerry", 30, 30);
+    assert_eq!(
+        next,
+        "http://localhost:7070/opds/search?query=tom+%26+jerry&limit=30&offset=30"
+    );
+    let mut feed = String::new();
+    link(&mut feed, "next", &next, ACQUISITION_TYPE);
+    assert!(feed.contains(
+        r#"href="http://localhost:7070/opds/search?query=tom+%26+jerry&amp;limit=30&amp;offset=30""#
+    ));
+}
@@ -0,0 +1,92 @@
+//! OpenAPI document of the http api, with a docs page to browse it.
+
+use crate::error::ErrorBody;
+use actix_web::{get, http::header, web, HttpResponse, Responder};
+use book_searcher_core::{
+    search::{
+        FacetCount, FacetField, Highlight, SearchHit, SearchMode, SearchResult, SortBy, SortOrder,
+        SuggestField,
+    },
+    Book, IndexStats,
+};
+use utoipa::{
+    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+    Modify, OpenApi,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Book Searcher"),
+    paths(
+        crate::search,
+        crate::search_index,
+        crate::list_indexes,
+        crate::suggest,
+        crate::suggest_index,
+        crate::book_by_id,
+        crate::book_by_md5,
+        crate::books_by_isbn,
+        crate::book_by_cid,
+        crate::reload,
+        crate::metrics::metrics,
+        crate::healthz,
+        crate::readyz,
+        crate::stats,
+        crate::opds::root,
+        crate::opds::opensearch,
+        crate::opds::search,
+    ),
+    components(schemas(
+        crate::SearchResponse,
+        SearchResult,
+        SearchHit,
+        Book,
+        Highlight,
+        FacetField,
+        FacetCount,
+        SearchMode,
+        SortBy,
+        SortOrder,
+        SuggestField,
+        ErrorBody,
+        crate::Status,
+        IndexStats,
+    )),
+    modifiers(&AdminToken),
+    tags(
+        (name = "search", description = "Search books"),
+        (name = "books", description = "Look up a book"),
+        (name = "opds", description = "OPDS catalog for e-reader apps"),
+        (name = "admin", description = "Manage the server"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Bearer token of the admin endpoints, set by `admin_token`.
+struct AdminToken;
+
+impl Modify for AdminToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+        }
+    }
+}
+
+pub fn services(cfg: &mut web::ServiceConfig) {
+    cfg.service(docs).service(
+        // the swagger ui assets are bundled into the binary
+        SwaggerUi::new("/docs/{_:.*}").url("/openapi.json", ApiDoc::openapi()),
+    );
+}
+
+#[get("/docs")]
+async fn docs() -> impl Responder {
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, "/docs/"))
+        .finish()
+}
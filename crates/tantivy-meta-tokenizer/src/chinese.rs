@@ -1,4 +1,4 @@
-use crate::MetaTokenStream;
+use crate::TokenizerOptions;
 use jieba_rs::{Jieba, TokenizeMode};
 use tantivy::tokenizer::Token;
 
@@ -6,84 +6,154 @@ lazy_static::lazy_static! {
     static ref JIEBA: Jieba = jieba_rs::Jieba::new();
 }
 
-pub fn token_stream(text: &str) -> MetaTokenStream {
-    let text = fast2s::convert(text);
+/// Positions taken by each word, the word, its pinyin and its pinyin initials.
+#[cfg(feature = "pinyin")]
+const WORD_STRIDE: usize = 3;
+#[cfg(not(feature = "pinyin"))]
+const WORD_STRIDE: usize = 1;
 
-    let mut indices = text.char_indices().collect::<Vec<_>>();
-    indices.push((text.len(), '\0'));
+/// Tokenize a run of Han text found at byte `offset` of the input,
+/// numbering the tokens from `position`.
+///
+/// Words are segmented and emitted in simplified Chinese, offsets point to the original text.
+pub fn tokenize(
+    text: &str,
+    offset: usize,
+    position: usize,
+    options: TokenizerOptions,
+    tokens: &mut Vec<Token>,
+) {
+    let simplified = fast2s::convert(text);
 
-    let origin_tokens = JIEBA.tokenize(&text, TokenizeMode::Search, false);
-    let origin_tokens_len = origin_tokens.len();
+    // fast2s converts char by char, so char indices of both texts match
+    let mut indices = text
+        .char_indices()
+        .map(|(idx, _)| offset + idx)
+        .collect::<Vec<_>>();
+    indices.push(offset + text.len());
 
-    #[cfg(not(feature = "pinyin"))]
-    let all_tokens_len = origin_tokens_len;
+    let words = JIEBA.tokenize(&simplified, TokenizeMode::Search, false);
 
-    #[cfg(feature = "pinyin")]
-    use itertools::Itertools;
-    #[cfg(feature = "pinyin")]
-    use pinyin::ToPinyinMulti;
-    #[cfg(feature = "pinyin")]
-    let chars_pinyins: Vec<(usize, String)> = text
-        .as_str()
-        .to_pinyin_multi()
-        .enumerate()
-        .filter_map(|(idx, pm)| {
-            pm.map(|pm| {
-                pm.into_iter()
-                    .map(move |p| (idx, p.plain().to_string()))
-                    .unique()
-            })
-        })
-        .flatten()
-        .collect();
-    #[cfg(feature = "pinyin")]
-    let all_tokens_len = origin_tokens_len * 2 + chars_pinyins.len();
-
-    let mut tokens = Vec::with_capacity(all_tokens_len);
-
-    for (token_idx, token) in origin_tokens.iter().enumerate() {
+    for (word_idx, word) in words.iter().enumerate() {
         tokens.push(Token {
-            offset_from: indices[token.start].0,
-            offset_to: indices[token.end].0,
-            text: token.word.to_string(),
-            position: token_idx * 2 + 1,
-            position_length: all_tokens_len,
+            offset_from: indices[word.start],
+            offset_to: indices[word.end],
+            text: word.word.to_string(),
+            position: position + word_idx * WORD_STRIDE,
+            position_length: 1,
         });
 
+        if options.keep_traditional {
+            let original = &text[indices[word.start] - offset..indices[word.end] - offset];
+            if original != word.word {
+                tokens.push(Token {
+                    offset_from: indices[word.start],
+                    offset_to: indices[word.end],
+                    text: original.to_string(),
+                    position: position + word_idx * WORD_STRIDE,
+                    position_length: 1,
+                });
+            }
+        }
+
         #[cfg(feature = "pinyin")]
         {
             // every token, not pinyin_multi to limit the index size
             use pinyin::ToPinyin;
-            let pinyin: String = token
-                .word
-                .to_pinyin()
-                .flatten()
-                .map(|p| p.plain())
-                .collect();
+            let pinyin: String = word.word.to_pinyin().flatten().map(|p| p.plain()).collect();
+
+            // kana has no pinyin without the `japanese` feature
+            if !pinyin.is_empty() {
+                tokens.push(Token {
+                    offset_from: indices[word.start],
+                    offset_to: indices[word.end],
+                    text: pinyin,
+                    position: position + word_idx * WORD_STRIDE + 1,
+                    position_length: 1,
+                });
+            }
 
-            tokens.push(Token {
-                offset_from: indices[token.start].0,
-                offset_to: indices[token.end].0,
-                text: pinyin,
-                position: token_idx * 2 + 2,
-                position_length: all_tokens_len,
-            });
+            let initials = pinyin_initials(word.word);
+            if options.pinyin_initials && initials.len() >= crate::MIN_PINYIN_INITIALS_LEN {
+                tokens.push(Token {
+                    offset_from: indices[word.start],
+                    offset_to: indices[word.end],
+                    text: format!("{}{initials}", crate::PINYIN_INITIALS_PREFIX),
+                    position: position + word_idx * WORD_STRIDE + 2,
+                    position_length: 1,
+                });
+            }
         }
     }
 
     #[cfg(feature = "pinyin")]
     {
         // every Chinese char
-        chars_pinyins.into_iter().for_each(|(idx, p)| {
-            tokens.push(Token {
-                offset_from: indices[idx].0,
-                offset_to: indices[idx + 1].0,
-                text: p,
-                position: origin_tokens_len * 2 + idx + 1,
-                position_length: all_tokens_len,
-            });
+        use itertools::Itertools;
+        use pinyin::ToPinyinMulti;
+        let position = position + words.len() * WORD_STRIDE;
+        for (idx, pinyin_multi) in simplified.as_str().to_pinyin_multi().enumerate() {
+            let Some(pinyin_multi) = pinyin_multi else {
+                continue;
+            };
+            for pinyin in pinyin_multi.into_iter().map(|p| p.plain()).unique() {
+                tokens.push(Token {
+                    offset_from: indices[idx],
+                    offset_to: indices[idx + 1],
+                    text: pinyin.to_string(),
+                    position: position + idx,
+                    position_length: 1,
+                });
+            }
+        }
+    }
+}
+
+/// Pinyin initials of each Han run, e.g. `^hlm` for 红楼梦, and of all runs together,
+/// e.g. `^stha` for 三体 黑暗, numbered from `position`.
+///
+/// They come after the tokens of the whole text, which are then at the same positions
+/// whether initials are emitted or not, as in queries. Initials already emitted for a word
+/// are skipped.
+#[cfg(feature = "pinyin")]
+pub fn tokenize_initials(runs: &[(usize, &str)], mut position: usize, tokens: &mut Vec<Token>) {
+    let run_initials: Vec<String> = runs
+        .iter()
+        .map(|(_, run)| pinyin_initials(&fast2s::convert(run)))
+        .collect();
+    let mut spans: Vec<(usize, usize, String)> = runs
+        .iter()
+        .zip(&run_initials)
+        .map(|(&(offset, run), initials)| (offset, offset + run.len(), initials.clone()))
+        .collect();
+    if let [first, .., last] = runs {
+        spans.push((first.0, last.0 + last.1.len(), run_initials.concat()));
+    }
+
+    for (offset_from, offset_to, initials) in spans {
+        let text = format!("{}{initials}", crate::PINYIN_INITIALS_PREFIX);
+        if initials.len() < crate::MIN_PINYIN_INITIALS_LEN
+            || tokens.iter().any(|token| token.text == text)
+        {
+            continue;
+        }
+        tokens.push(Token {
+            offset_from,
+            offset_to,
+            text,
+            position,
+            position_length: 1,
         });
+        position += 1;
     }
+}
 
-    MetaTokenStream { tokens, index: 0 }
+/// First letters of the pinyin of each Chinese char.
+#[cfg(feature = "pinyin")]
+fn pinyin_initials(text: &str) -> String {
+    use pinyin::ToPinyin;
+    text.to_pinyin()
+        .flatten()
+        .map(|p| p.first_letter())
+        .collect()
 }
@@ -0,0 +1,125 @@
+use crate::{utils, utils::Script, MetaTokenStream};
+use tantivy::tokenizer::{
+    AsciiFoldingFilter, Language, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer,
+    TextAnalyzer, Token, TokenStream, Tokenizer,
+};
+
+/// Options of the latin analyzer, used for the stemmed subfields.
+#[derive(Clone, Copy, Debug)]
+pub struct LatinOptions {
+    /// Fold accented chars to ascii, e.g. `café` to `cafe`.
+    pub ascii_folding: bool,
+    /// Snowball stemmer language, no stemming when `None`.
+    pub stemmer: Option<Language>,
+}
+
+impl Default for LatinOptions {
+    fn default() -> Self {
+        Self {
+            ascii_folding: true,
+            stemmer: Some(Language::English),
+        }
+    }
+}
+
+pub fn get_latin_tokenizer(options: LatinOptions) -> TextAnalyzer {
+    let mut builder = TextAnalyzer::builder(LatinTokenizer::default())
+        .filter(RemoveLongFilter::limit(20))
+        .filter(LowerCaser)
+        .dynamic();
+    if options.ascii_folding {
+        builder = builder.filter_dynamic(AsciiFoldingFilter);
+    }
+    if let Some(language) = options.stemmer {
+        builder = builder.filter_dynamic(Stemmer::new(language));
+    }
+    builder.build()
+}
+
+/// Snowball stemmer of a language name like `english`, or a code like `en`.
+pub fn stemmer_language(name: &str) -> Option<Language> {
+    let language = match name.trim().to_ascii_lowercase().as_str() {
+        "arabic" | "ar" => Language::Arabic,
+        "danish" | "da" => Language::Danish,
+        "dutch" | "nl" => Language::Dutch,
+        "english" | "en" => Language::English,
+        "finnish" | "fi" => Language::Finnish,
+        "french" | "fr" => Language::French,
+        "german" | "de" => Language::German,
+        "greek" | "el" => Language::Greek,
+        "hungarian" | "hu" => Language::Hungarian,
+        "italian" | "it" => Language::Italian,
+        "norwegian" | "no" | "nb" => Language::Norwegian,
+        "portuguese" | "pt" => Language::Portuguese,
+        "romanian" | "ro" => Language::Romanian,
+        "russian" | "ru" => Language::Russian,
+        "spanish" | "es" => Language::Spanish,
+        "swedish" | "sv" => Language::Swedish,
+        "tamil" | "ta" => Language::Tamil,
+        "turkish" | "tr" => Language::Turkish,
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Tokenizer of the latin runs of a text, other scripts are skipped.
+#[derive(Clone, Default)]
+pub struct LatinTokenizer {
+    latin: SimpleTokenizer,
+}
+
+impl Tokenizer for LatinTokenizer {
+    type TokenStream<'a> = MetaTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> MetaTokenStream {
+        let mut tokens = Vec::new();
+        for (offset, script, run) in utils::script_runs(text) {
+            if script == Script::Latin {
+                let position = tokens.last().map_or(0, |token: &Token| token.position + 1);
+                tokenize(&mut self.latin, run, offset, position, &mut tokens);
+            }
+        }
+        MetaTokenStream { tokens, index: 0 }
+    }
+}
+
+/// Tokenize a run of latin text found at byte `offset` of the input,
+/// numbering the tokens from `position`. Lowercasing is left to the `LowerCaser` filter.
+pub fn tokenize(
+    latin: &mut SimpleTokenizer,
+    text: &str,
+    offset: usize,
+    position: usize,
+    tokens: &mut Vec<Token>,
+) {
+    let mut stream = latin.token_stream(text);
+    while let Some(token) = stream.next() {
+        tokens.push(Token {
+            offset_from: offset + token.offset_from,
+            offset_to: offset + token.offset_to,
+            text: token.text.clone(),
+            position: position + token.position,
+            position_length: token.position_length,
+        });
+    }
+}
+
+#[test]
+fn test_latin_tokenizer() {
+    let mut tokenizer = get_latin_tokenizer(LatinOptions::default());
+    let text = "三体 Café Programming";
+    let mut stream = tokenizer.token_stream(text);
+    let mut tokens = Vec::new();
+    while let Some(token) = stream.next() {
+        let original = &text[token.offset_from..token.offset_to];
+        tokens.push((token.text.clone(), original.to_owned(), token.position));
+    }
+    assert_eq!(
+        tokens,
+        [("cafe", "Café", 0), ("program", "Programming", 1)].map(|(word, original, position)| (
+            word.to_owned(),
+            original.to_owned(),
+            position
+        ))
+    );
+}
@@ -3,18 +3,72 @@
 
 use stop_word::STOP_WORDS;
 use tantivy::tokenizer::{
-    BoxTokenStream, LowerCaser, RemoveLongFilter, SimpleTokenizer, StopWordFilter, TextAnalyzer,
-    Token, TokenStream, Tokenizer,
+    LowerCaser, RemoveLongFilter, SimpleTokenizer, StopWordFilter, TextAnalyzer, Token,
+    TokenStream, Tokenizer,
 };
+use utils::Script;
 mod chinese;
+mod latin;
+#[cfg(feature = "lindera")]
+mod lindera;
 mod stop_word;
 pub mod utils;
 
+pub use latin::{get_latin_tokenizer, stemmer_language, LatinOptions};
+pub use tantivy::tokenizer::Language;
+
 pub const META_TOKENIZER: &str = "meta_tokenizer";
+/// Name of the latin analyzer of the stemmed subfields.
+pub const META_LATIN_TOKENIZER: &str = "meta_latin_tokenizer";
+
+/// Prefix of pinyin initials tokens, e.g. `^hlm` for 红楼梦.
+///
+/// Latin text never yields it, so initials don't match latin words unless asked for.
+#[cfg(feature = "pinyin")]
+pub const PINYIN_INITIALS_PREFIX: &str = "^";
+
+/// Shortest pinyin initials emitted, single letters and pairs would match far too much.
+#[cfg(feature = "pinyin")]
+pub const MIN_PINYIN_INITIALS_LEN: usize = 3;
+
+/// Options of the meta tokenizer, the defaults are meant for indexing.
+#[derive(Clone, Copy, Debug)]
+pub struct TokenizerOptions {
+    /// Also emit the original traditional Chinese words, at the same positions as the simplified ones.
+    pub keep_traditional: bool,
+    /// Also emit the pinyin initials of words, of Han runs and of the whole text,
+    /// with the `pinyin` feature.
+    pub pinyin_initials: bool,
+}
+
+impl Default for TokenizerOptions {
+    fn default() -> Self {
+        Self {
+            keep_traditional: true,
+            pinyin_initials: true,
+        }
+    }
+}
+
+impl TokenizerOptions {
+    /// Options to tokenize queries: a phrase query needs all its tokens to match,
+    /// so tokens only found in some indexed texts are left out.
+    pub fn for_query() -> Self {
+        Self {
+            keep_traditional: false,
+            pinyin_initials: false,
+        }
+    }
+}
 
 pub fn get_tokenizer() -> TextAnalyzer {
+    get_tokenizer_with(TokenizerOptions::default())
+}
+
+pub fn get_tokenizer_with(options: TokenizerOptions) -> TextAnalyzer {
     TextAnalyzer::builder(MetaTokenizer {
         latin: SimpleTokenizer::default(),
+        options,
     })
     .filter(RemoveLongFilter::limit(20))
     // .filter(AsciiFoldingFilter) // spammy search results
@@ -28,6 +82,7 @@ pub fn get_tokenizer() -> TextAnalyzer {
 #[derive(Clone)]
 pub struct MetaTokenizer {
     latin: SimpleTokenizer,
+    options: TokenizerOptions,
 }
 
 pub struct MetaTokenStream {
@@ -55,20 +110,186 @@ impl TokenStream for MetaTokenStream {
 }
 
 impl Tokenizer for MetaTokenizer {
-    type TokenStream<'a> = BoxTokenStream<'a>;
-
-    fn token_stream<'a>(&'a mut self, text: &'a str) -> BoxTokenStream<'a> {
-        if text.is_empty() {
-            return BoxTokenStream::new(MetaTokenStream {
-                tokens: vec![],
-                index: 0,
-            });
+    type TokenStream<'a> = MetaTokenStream;
+
+    /// Split the text into script runs and tokenize each run on its own,
+    /// continuing offsets and positions from one run to the next.
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> MetaTokenStream {
+        let mut tokens = Vec::new();
+        let mut position = 0;
+        #[cfg(feature = "pinyin")]
+        let mut han_runs = Vec::new();
+        for (offset, script, run) in utils::script_runs(text) {
+            let start = tokens.len();
+            #[cfg(feature = "pinyin")]
+            if script == Script::Han {
+                han_runs.push((offset, run));
+            }
+            match script {
+                Script::Han => chinese::tokenize(run, offset, position, self.options, &mut tokens),
+                #[cfg(feature = "japanese")]
+                Script::Japanese => {
+                    lindera::tokenize_japanese(run, offset, position, self.options, &mut tokens)
+                }
+                // jieba splits kana into single chars, still better than one token
+                #[cfg(not(feature = "japanese"))]
+                Script::Japanese => {
+                    chinese::tokenize(run, offset, position, self.options, &mut tokens)
+                }
+                #[cfg(feature = "korean")]
+                Script::Hangul => lindera::tokenize_korean(run, offset, position, &mut tokens),
+                #[cfg(not(feature = "korean"))]
+                Script::Hangul => {
+                    latin::tokenize(&mut self.latin, run, offset, position, &mut tokens)
+                }
+                Script::Latin => {
+                    latin::tokenize(&mut self.latin, run, offset, position, &mut tokens)
+                }
+            }
+            if let Some(next) = tokens[start..].iter().map(|token| token.position + 1).max() {
+                position = next;
+            }
+        }
+        #[cfg(feature = "pinyin")]
+        if self.options.pinyin_initials {
+            chinese::tokenize_initials(&han_runs, position, &mut tokens);
         }
+        MetaTokenStream { tokens, index: 0 }
+    }
+}
+
+#[test]
+fn test_mixed_script() {
+    let tokens = |text: &str| {
+        let mut tokenizer = get_tokenizer();
+        let mut stream = tokenizer.token_stream(text);
+        let mut tokens = Vec::new();
+        while let Some(token) = stream.next() {
+            tokens.push(token.clone());
+        }
+        tokens
+    };
+
+    let text = "Python编程：从入门到实践";
+    let words: Vec<_> = tokens(text)
+        .into_iter()
+        .map(|token| (token.text, &text[token.offset_from..token.offset_to]))
+        .collect();
+    assert_eq!(words[0], ("python".to_owned(), "Python"));
+    assert_eq!(words[1], ("编程".to_owned(), "编程"));
+    assert!(words.contains(&("入门".to_owned(), "入门")));
+
+    let tokens = tokens("三体 The Three-Body Problem");
+    let (latin, han): (Vec<_>, Vec<_>) = tokens
+        .iter()
+        .partition(|token| token.offset_from > "三体".len());
+    let latin_positions: Vec<_> = latin.iter().map(|token| token.position).collect();
+    let first = han.iter().map(|token| token.position).max().unwrap() + 1;
+    assert_eq!(latin_positions, (first..first + 4).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_keep_traditional() {
+    let words = |options| {
+        let mut tokenizer = get_tokenizer_with(options);
+        let text = "三體";
+        let mut stream = tokenizer.token_stream(text);
+        let mut words = Vec::new();
+        while let Some(token) = stream.next() {
+            if !token.text.is_ascii() {
+                let original = &text[token.offset_from..token.offset_to];
+                words.push((token.text.clone(), original.to_owned(), token.position));
+            }
+        }
+        words
+    };
+
+    let simplified = words(TokenizerOptions {
+        keep_traditional: false,
+        ..Default::default()
+    });
+    let all = words(TokenizerOptions::default());
+    assert!(simplified.iter().all(|word| all.contains(word)));
+    let (_, original, position) = simplified.iter().find(|(word, ..)| word == "体").unwrap();
+    assert!(all.contains(&("體".to_owned(), original.clone(), *position)));
+}
+
+#[test]
+fn test_japanese() {
+    let words = |text: &str| {
+        let mut tokenizer = get_tokenizer_with(TokenizerOptions::for_query());
+        let mut stream = tokenizer.token_stream(text);
+        let mut words = Vec::new();
+        while let Some(token) = stream.next() {
+            assert!(!token.text.is_empty());
+            words.push(token.text.clone());
+        }
+        words
+    };
+
+    // kanji are simplified whether they are next to kana or not
+    assert_eq!(words("進撃")[0], "进击");
+    assert!(words("進撃の巨人").contains(&"进击".to_owned()));
+
+    // jieba splits kana into single chars
+    #[cfg(not(feature = "japanese"))]
+    assert!(words("ノルウェイの森").contains(&"ノ".to_owned()));
+}
+
+#[cfg(feature = "pinyin")]
+#[test]
+fn test_pinyin_initials() {
+    let initials = |text: &str| {
+        let mut tokenizer = get_tokenizer();
+        let mut stream = tokenizer.token_stream(text);
+        let mut initials = Vec::new();
+        while let Some(token) = stream.next() {
+            if let Some(letters) = token.text.strip_prefix(PINYIN_INITIALS_PREFIX) {
+                initials.push(letters.to_owned());
+            }
+        }
+        initials
+    };
+
+    // the word and its run have the same initials, emitted once
+    assert_eq!(initials("红楼梦"), ["hlm"]);
+    assert_eq!(initials("紅樓夢"), ["hlm"]);
+    // initials of the whole text across runs
+    let text = initials("三体 黑暗森林");
+    assert!(text.contains(&"sthasl".to_owned()));
+    assert!(text.contains(&"hasl".to_owned()));
+    // too short
+    assert!(initials("三体").is_empty());
+    assert!(initials("书").is_empty());
+    assert!(text
+        .iter()
+        .all(|initials| initials.len() >= MIN_PINYIN_INITIALS_LEN));
+}
 
-        if utils::is_chinese(text) {
-            return BoxTokenStream::new(chinese::token_stream(text));
+#[cfg(feature = "pinyin")]
+#[test]
+fn test_positions_without_initials() {
+    let tokens = |options| {
+        let mut tokenizer = get_tokenizer_with(options);
+        let mut stream = tokenizer.token_stream("红楼梦 黑暗森林 Dark Forest");
+        let mut tokens = Vec::new();
+        while let Some(token) = stream.next() {
+            tokens.push((token.text.clone(), token.position));
         }
+        tokens
+    };
 
-        return BoxTokenStream::new(self.latin.token_stream(text));
+    // a phrase query across runs needs the words at the same positions as indexed
+    let indexed = tokens(TokenizerOptions::default());
+    let query = tokens(TokenizerOptions::for_query());
+    assert!(query.iter().all(|token| indexed.contains(token)));
+    let position = |tokens: &[(String, usize)], word: &str| {
+        tokens.iter().find(|(text, _)| text == word).unwrap().1
+    };
+    for word in ["黑暗", "dark", "forest"] {
+        assert_eq!(position(&indexed, word), position(&query, word));
     }
+    assert!(indexed
+        .iter()
+        .any(|(text, _)| text.starts_with(PINYIN_INITIALS_PREFIX)));
 }
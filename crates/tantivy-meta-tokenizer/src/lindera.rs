@@ -0,0 +1,92 @@
+use lindera_core::mode::Mode;
+use lindera_dictionary::{DictionaryConfig, DictionaryKind};
+use lindera_tokenizer::tokenizer::{Tokenizer, TokenizerConfig};
+use tantivy::tokenizer::Token;
+
+#[cfg(feature = "korean")]
+use crate::latin;
+#[cfg(feature = "japanese")]
+use crate::{chinese, TokenizerOptions};
+#[cfg(feature = "korean")]
+use tantivy::tokenizer::SimpleTokenizer;
+
+#[cfg(feature = "japanese")]
+lazy_static::lazy_static! {
+    static ref JAPANESE: Tokenizer = new_tokenizer(DictionaryKind::IPADIC);
+}
+
+#[cfg(feature = "korean")]
+lazy_static::lazy_static! {
+    static ref KOREAN: Tokenizer = new_tokenizer(DictionaryKind::KoDic);
+}
+
+fn new_tokenizer(kind: DictionaryKind) -> Tokenizer {
+    Tokenizer::from_config(TokenizerConfig {
+        dictionary: DictionaryConfig {
+            kind: Some(kind),
+            path: None,
+        },
+        user_dictionary: None,
+        mode: Mode::Normal,
+    })
+    .expect("embedded lindera dictionary")
+}
+
+/// Tokenize a run of kana and kanji with the IPADIC dictionary.
+///
+/// Kanji words are emitted in simplified Chinese like Han runs, so 進撃 matches
+/// whether it is found next to kana or not, the original words are kept by `keep_traditional`.
+#[cfg(feature = "japanese")]
+pub fn tokenize_japanese(
+    text: &str,
+    offset: usize,
+    position: usize,
+    options: TokenizerOptions,
+    tokens: &mut Vec<Token>,
+) {
+    let words = match JAPANESE.tokenize(text) {
+        Ok(words) => words,
+        Err(err) => {
+            log::warn!("Failed to tokenize {text:?} with lindera, falling back to jieba: {err}");
+            chinese::tokenize(text, offset, position, options, tokens);
+            return;
+        }
+    };
+    for (word_idx, word) in words.into_iter().enumerate() {
+        let new_token = |text: String| Token {
+            offset_from: offset + word.byte_start,
+            offset_to: offset + word.byte_end,
+            text,
+            position: position + word_idx,
+            position_length: 1,
+        };
+        let simplified = fast2s::convert(&word.text);
+        let original =
+            (options.keep_traditional && simplified != word.text).then(|| word.text.to_string());
+        tokens.push(new_token(simplified));
+        tokens.extend(original.map(new_token));
+    }
+}
+
+/// Tokenize a run of hangul with the ko-dic dictionary.
+#[cfg(feature = "korean")]
+pub fn tokenize_korean(text: &str, offset: usize, position: usize, tokens: &mut Vec<Token>) {
+    let words = match KOREAN.tokenize(text) {
+        Ok(words) => words,
+        Err(err) => {
+            log::warn!("Failed to tokenize {text:?} with lindera, falling back to words: {err}");
+            let mut latin = SimpleTokenizer::default();
+            latin::tokenize(&mut latin, text, offset, position, tokens);
+            return;
+        }
+    };
+    for (word_idx, word) in words.into_iter().enumerate() {
+        tokens.push(Token {
+            offset_from: offset + word.byte_start,
+            offset_to: offset + word.byte_end,
+            text: word.text.to_string(),
+            position: position + word_idx,
+            position_length: 1,
+        });
+    }
+}
@@ -1,3 +1,118 @@
+/// Script of a run of text, deciding which tokenizer handles it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Script {
+    Han,
+    /// Kana, and Han next to kana.
+    Japanese,
+    Hangul,
+    /// Everything else, split on non-alphanumeric chars.
+    Latin,
+}
+
+impl Script {
+    pub const fn of(c: char) -> Self {
+        if is_chinese_char(c) {
+            Self::Han
+        } else if is_kana_char(c) {
+            Self::Japanese
+        } else if is_hangul_char(c) {
+            Self::Hangul
+        } else {
+            Self::Latin
+        }
+    }
+}
+
+/// Split the text into maximal runs of the same script, with the byte offset of each run.
+///
+/// Han runs next to kana are kanji and belong to Japanese runs, e.g. 進撃 in 進撃の巨人,
+/// while 三体 in `三体 ノート` stays Han.
+pub fn script_runs(text: &str) -> impl Iterator<Item = (usize, Script, &str)> + '_ {
+    let runs: Vec<_> = char_runs(text, Script::of).collect();
+    let scripts: Vec<_> = runs
+        .iter()
+        .enumerate()
+        .map(|(idx, &(_, script, _))| {
+            let next_to_kana = |idx: Option<usize>| {
+                idx.and_then(|idx| runs.get(idx))
+                    .map_or(false, |&(_, script, _)| script == Script::Japanese)
+            };
+            if script == Script::Han
+                && (next_to_kana(idx.checked_sub(1)) || next_to_kana(Some(idx + 1)))
+            {
+                Script::Japanese
+            } else {
+                script
+            }
+        })
+        .collect();
+
+    let mut runs = runs.into_iter().zip(scripts).peekable();
+    std::iter::from_fn(move || {
+        let ((start, _, run), script) = runs.next()?;
+        let mut end = start + run.len();
+        while let Some(&((idx, _, run), _)) = runs.peek().filter(|(_, next)| *next == script) {
+            end = idx + run.len();
+            runs.next();
+        }
+        Some((start, script, &text[start..end]))
+    })
+}
+
+/// Split the text into maximal runs of chars with the same script.
+fn char_runs(
+    text: &str,
+    script_of: impl Fn(char) -> Script,
+) -> impl Iterator<Item = (usize, Script, &str)> {
+    let mut chars = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        let (start, c) = chars.next()?;
+        let script = script_of(c);
+        let mut end = start + c.len_utf8();
+        while let Some(&(idx, c)) = chars.peek() {
+            if script_of(c) != script {
+                break;
+            }
+            end = idx + c.len_utf8();
+            chars.next();
+        }
+        Some((start, script, &text[start..end]))
+    })
+}
+
+pub fn is_japanese(text: &str) -> bool {
+    text.contains(is_kana_char)
+}
+
+pub fn is_korean(text: &str) -> bool {
+    text.contains(is_hangul_char)
+}
+
+/// Hiragana, katakana and their extensions, halfwidth katakana.
+#[inline(always)]
+pub const fn is_kana_char(c: char) -> bool {
+    matches!(c as u32,
+        0x3041..=0x309F |
+        0x30A0..=0x30FF |
+        0x31F0..=0x31FF |
+        0xFF66..=0xFF9F |
+        0x1B000..=0x1B16F
+    )
+}
+
+/// Hangul syllables and jamo.
+#[inline(always)]
+pub const fn is_hangul_char(c: char) -> bool {
+    matches!(c as u32,
+        0xAC00..=0xD7A3 |
+        0x1100..=0x11FF |
+        0x3130..=0x318F |
+        0xA960..=0xA97F |
+        0xD7B0..=0xD7FF |
+        0xFFA0..=0xFFDC
+    )
+}
+
 pub fn is_chinese(text: &str) -> bool {
     let chinese_char_count = text.matches(is_chinese_char).count();
     // chinese_char_count as f32 / text.chars().count() as f32 > 0.3
@@ -26,3 +141,49 @@ pub const fn is_chinese_char(c: char) -> bool {
         0x31A0..=0x31BA
     )
 }
+
+#[test]
+fn test_script_runs() {
+    let runs = |text| {
+        script_runs(text)
+            .map(|(offset, script, run)| (offset, script, run.to_owned()))
+            .collect::<Vec<_>>()
+    };
+    let run = |offset, script, run: &str| (offset, script, run.to_owned());
+
+    assert_eq!(
+        runs("Python编程"),
+        [run(0, Script::Latin, "Python"), run(6, Script::Han, "编程")]
+    );
+    assert_eq!(runs("進撃の巨人"), [run(0, Script::Japanese, "進撃の巨人")]);
+    assert_eq!(
+        runs("三体 ノート"),
+        [
+            run(0, Script::Han, "三体"),
+            run(6, Script::Latin, " "),
+            run(7, Script::Japanese, "ノート")
+        ]
+    );
+    assert_eq!(
+        runs("채식주의자 2"),
+        [
+            run(0, Script::Hangul, "채식주의자"),
+            run(15, Script::Latin, " 2")
+        ]
+    );
+    assert!(runs("").is_empty());
+}
+
+#[test]
+fn test_script_chars() {
+    assert!(is_kana_char('あ'));
+    assert!(is_kana_char('ノ'));
+    assert!(is_kana_char('ｱ'));
+    assert!(!is_kana_char('撃'));
+    assert!(!is_kana_char('a'));
+
+    assert!(is_hangul_char('한'));
+    assert!(is_hangul_char('ㄱ'));
+    assert!(!is_hangul_char('あ'));
+    assert!(!is_hangul_char('三'));
+}
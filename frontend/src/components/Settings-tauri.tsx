@@ -62,6 +62,19 @@ const Settings: React.FC = () => {
       });
   }, [isOpen]);
   const toast = useToast();
+
+  React.useEffect(() => {
+    invoke('index_outdated').then((outdated) => {
+      outdated &&
+        toast({
+          title: t('settings.indexing.outdated'),
+          status: 'warning',
+          position: 'top',
+          isClosable: true
+        });
+    });
+  }, []);
+
   const onSubmit = async (newConfig: Config) => {
     setSubmitting(true);
 